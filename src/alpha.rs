@@ -0,0 +1,141 @@
+use crate::hit::{Collide, HitRecord, Hittable, Intersects};
+use crate::ray::Ray;
+use crate::texture::FloatTexture;
+use crate::vec3::Point3;
+use rand::Rng;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy)]
+pub enum AlphaMode {
+    // kept with probability equal to the opacity, so partial opacity averages out
+    Stochastic,
+    // kept wherever the opacity reaches the cutoff, hard edged leaves and fences
+    Threshold(f64),
+}
+
+/* Cut-out transparency for any object. Hits where the opacity texture says there is
+ *  nothing are thrown away inside `hit` and the search carries on past them, so the
+ *  ray goes straight through without a bounce being spent on it.
+ */
+pub struct AlphaMask {
+    object: Arc<dyn Collide + Sync + Send>,
+    opacity: Arc<dyn FloatTexture + Sync + Send>,
+    mode: AlphaMode,
+}
+
+impl AlphaMask {
+    pub fn new(object: Arc<dyn Collide + Sync + Send>, opacity: Arc<dyn FloatTexture + Sync + Send>, mode: AlphaMode) -> Self {
+        Self { object, opacity, mode }
+    }
+
+    fn keeps(&self, record: &HitRecord) -> bool {
+        let alpha = self.opacity.value(record);
+        match self.mode {
+            AlphaMode::Stochastic => alpha >= 1.0 || alpha > rand::thread_rng().gen_range(0.0..1.0),
+            AlphaMode::Threshold(cutoff) => alpha >= cutoff,
+        }
+    }
+}
+
+impl Hittable for AlphaMask {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_record: &mut HitRecord) -> bool {
+        let mut t_min = t_min;
+        while self.object.hit(ray, t_min, t_max, hit_record) {
+            if self.keeps(hit_record) {
+                return true;
+            }
+            // nudged past so the same hit can't come back
+            t_min = hit_record.t + 1e-7 * hit_record.t.abs().max(1.0);
+        }
+        false
+    }
+}
+
+impl Intersects for AlphaMask {
+    fn center(&self) -> Point3 {
+        self.object.center()
+    }
+
+    fn radius(&self) -> f64 {
+        self.object.radius()
+    }
+}
+
+impl Collide for AlphaMask {
+
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{AlphaMask, AlphaMode};
+    use crate::hit::{HitRecord, Hittable, Lambertian, Sphere};
+    use crate::mesh::{MeshData, TriangleMesh};
+    use crate::ray::Ray;
+    use crate::texture::{Channel, FloatTexture, ImageChannel, ImageTexture};
+    use crate::vec3::{Colour, Point3, Vec3};
+    use std::sync::Arc;
+
+    // see-through on the +z half
+    struct BackHalf;
+
+    impl FloatTexture for BackHalf {
+        fn value(&self, record: &HitRecord) -> f64 {
+            if record.point().z() > 0.0 { 0.0 } else { 1.0 }
+        }
+    }
+
+    #[test]
+    fn masked_hits_are_skipped() {
+        let material = Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5)));
+        let sphere = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut record = HitRecord::new();
+
+        let cutout = AlphaMask::new(sphere.clone(), Arc::new(BackHalf), AlphaMode::Threshold(0.5));
+        assert!(cutout.hit(&ray, 0.001, f64::INFINITY, &mut record));
+        assert!((record.t - 6.0).abs() < 1e-9);
+
+        let invisible = AlphaMask::new(sphere.clone(), Arc::new(0.0), AlphaMode::Threshold(0.5));
+        assert!(!invisible.hit(&ray, 0.001, f64::INFINITY, &mut record));
+
+        let half = AlphaMask::new(sphere, Arc::new(0.5), AlphaMode::Stochastic);
+        let mut front = 0;
+        for _ in 0..10_000 {
+            if half.hit(&ray, 0.001, f64::INFINITY, &mut record) && record.t < 5.0 {
+                front += 1;
+            }
+        }
+        assert!((4_500..5_500).contains(&front), "{front}");
+    }
+
+    #[test]
+    fn opacity_from_a_png_alpha_channel() {
+        // 2 by 1 rgba, a clear left half and an opaque right one
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 2, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0, 255, 0, 0, 0, 255, 0, 255]).unwrap();
+        writer.finish().unwrap();
+        let image = Arc::new(ImageTexture::decode(&png).unwrap());
+
+        // a 2 by 1 card in the z = 0 plane with the image stretched over it
+        let data = MeshData {
+            positions: vec![Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0), Point3::new(2.0, 1.0, 0.0), Point3::new(0.0, 1.0, 0.0)],
+            uvs: Some(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]),
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            ..MeshData::default()
+        };
+        let card = Arc::new(TriangleMesh::new(data, Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5)))));
+        let leaf = AlphaMask::new(card, Arc::new(ImageChannel::new(image, Channel::Alpha)), AlphaMode::Threshold(0.5));
+
+        let mut record = HitRecord::new();
+        let through = |x: f64, record: &mut HitRecord| {
+            !leaf.hit(&Ray::new(Point3::new(x, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::INFINITY, record)
+        };
+        assert!(through(0.5, &mut record));
+        assert!(!through(1.5, &mut record));
+    }
+}
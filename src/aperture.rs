@@ -0,0 +1,164 @@
+use crate::constants::{pi, DegToRad};
+use crate::texture::ImageTexture;
+use crate::vec3::Vec3;
+use rand::Rng;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/* The shape of the lens opening, which is the shape out of focus highlights take.
+ *  Samples land in the unit disk at z = 0 and the camera scales them by its lens
+ *  radius.
+ */
+#[derive(Clone, Default)]
+pub enum Aperture {
+    // a perfectly round iris
+    #[default]
+    Circle,
+    // straight bladed iris, rotation in degrees turning the first corner off +u
+    Polygon { blades: usize, rotation: f64 },
+    // anything else, from a grey scale picture of the opening
+    Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    pub fn polygon(blades: usize, rotation: f64) -> Self {
+        Aperture::Polygon { blades: blades.max(3), rotation }
+    }
+
+    pub fn sample(&self, rng: &mut impl Rng) -> Vec3 {
+        match self {
+            Aperture::Circle => loop {
+                let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
+                if p.length_squared() < 1.0 {
+                    break p;
+                }
+            },
+            Aperture::Polygon { blades, rotation } => {
+                // the blades cut the disk into equal triangles about the centre
+                let wedge = 2.0 * pi / *blades as f64;
+                let start = rotation.deg_to_rad() + wedge * rng.gen_range(0..*blades) as f64;
+                let (a, b) = (Vec3::new(start.cos(), start.sin(), 0.0), Vec3::new((start + wedge).cos(), (start + wedge).sin(), 0.0));
+                let (r, t) = (rng.gen_range(0.0f64..1.0).sqrt(), rng.gen_range(0.0..1.0));
+                r * ((1.0 - t) * a + t * b)
+            }
+            Aperture::Mask(mask) => mask.sample(rng),
+        }
+    }
+}
+
+/* A picture of the aperture, the brighter a pixel the more light gets through there.
+ *  The picture is centred on the lens with its longer side across the diameter.
+ */
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    // running total of the pixel values, row by row from the top
+    cdf: Vec<f64>,
+}
+
+impl ApertureMask {
+    // None unless there is a value per pixel and some light gets through
+    pub fn new(width: usize, height: usize, values: &[f64]) -> Option<Self> {
+        if width == 0 || height == 0 || values.len() != width * height {
+            return None;
+        }
+        let cdf: Vec<f64> = values
+            .iter()
+            .scan(0.0, |total, value| {
+                *total += value.max(0.0);
+                Some(*total)
+            })
+            .collect();
+        (*cdf.last()? > 0.0).then_some(Self { width, height, cdf })
+    }
+
+    /* @brief Reads a picture of the opening, a .pgm (P2 or P5), .png or .jpg
+     */
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_image(&ImageTexture::load_linear(path)?)
+    }
+
+    // a coloured picture lets through the mean of its channels
+    pub fn from_image(image: &ImageTexture) -> io::Result<Self> {
+        let (width, height) = image.size();
+        let values: Vec<f64> = image.pixels().iter().map(|c| (c.x() + c.y() + c.z()) / 3.0).collect();
+        Self::new(width, height, &values)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "aperture: no light gets through"))
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> Vec3 {
+        let total = self.cdf[self.cdf.len() - 1];
+        let x = rng.gen_range(0.0..total);
+        let pixel = self.cdf.partition_point(|&c| c <= x).min(self.cdf.len() - 1);
+        let (i, j) = (pixel % self.width, pixel / self.width);
+        let scale = 2.0 / self.width.max(self.height) as f64;
+        let x = (i as f64 + rng.gen_range(0.0..1.0) - self.width as f64 / 2.0) * scale;
+        let y = (self.height as f64 / 2.0 - j as f64 - rng.gen_range(0.0..1.0)) * scale;
+        Vec3::new(x, y, 0.0)
+    }
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{Aperture, ApertureMask};
+    use crate::texture::ImageTexture;
+    use std::io;
+    use std::sync::Arc;
+
+    fn decode(bytes: &[u8]) -> io::Result<ApertureMask> {
+        ApertureMask::from_image(&ImageTexture::decode_linear(bytes)?)
+    }
+
+    #[test]
+    fn samples_stay_inside_the_opening() {
+        let mut rng = rand::thread_rng();
+        // the inscribed circle of a hexagon reaches cos 30 of the way out
+        let hexagon = Aperture::polygon(6, 0.0);
+        let (mut edge, n) = (0, 20_000);
+        for _ in 0..n {
+            let p = hexagon.sample(&mut rng);
+            assert!(p.length() <= 1.0 + 1e-12);
+            let angle = p.y().atan2(p.x()).rem_euclid(std::f64::consts::PI / 3.0) - std::f64::consts::PI / 6.0;
+            assert!(p.length() * angle.cos() <= (std::f64::consts::PI / 6.0).cos() + 1e-12);
+            if p.length() > 0.9 {
+                edge += 1;
+            }
+        }
+        assert!(edge > 0);
+
+        // only the top right pixel lets light through
+        let mask = decode(b"P2\n# a corner\n2 2\n255\n0 255\n0 0\n").unwrap();
+        let mask = Aperture::Mask(Arc::new(mask));
+        for _ in 0..1000 {
+            let p = mask.sample(&mut rng);
+            assert!((0.0..=1.0).contains(&p.x()) && (0.0..=1.0).contains(&p.y()));
+        }
+        let binary = decode(b"P5 2 1 255\n\x00\x80").unwrap();
+        assert!(Aperture::Mask(Arc::new(binary)).sample(&mut rng).x() >= 0.0);
+        assert!(decode(b"P2 1 1 255 0").is_err());
+
+        // and the same from a png, lit on the right
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 2, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0, 255]).unwrap();
+        writer.finish().unwrap();
+        let mask = Aperture::Mask(Arc::new(decode(&png).unwrap()));
+        assert!((0..1000).all(|_| mask.sample(&mut rng).x() >= 0.0));
+    }
+
+    #[test]
+    fn mask_samples_follow_brightness() {
+        let mut rng = rand::thread_rng();
+        // the right pixel lets three times the light of the left one through
+        let mask = Aperture::Mask(Arc::new(ApertureMask::new(2, 1, &[1.0, 3.0]).unwrap()));
+        let n = 40_000;
+        let left = (0..n).filter(|_| mask.sample(&mut rng).x() < 0.0).count();
+        let share = left as f64 / n as f64;
+        assert!((share - 0.25).abs() < 0.02, "{share} of the samples on the left");
+    }
+}
@@ -0,0 +1,192 @@
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+// primitives per leaf before a node gets split
+const MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    pub fn new(min: Point3, max: Point3) -> Self {
+        Self { min, max }
+    }
+
+    // inverted box, anything unioned into it replaces it
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points(points: &[Point3]) -> Self {
+        points.iter().fold(Self::empty(), |b, p| b.grow(*p))
+    }
+
+    pub fn grow(&self, p: Point3) -> Self {
+        Self {
+            min: Vec3::new(self.min.x().min(p.x()), self.min.y().min(p.y()), self.min.z().min(p.z())),
+            max: Vec3::new(self.max.x().max(p.x()), self.max.y().max(p.y()), self.max.z().max(p.z())),
+        }
+    }
+
+    pub fn union(a: Aabb, b: Aabb) -> Self {
+        a.grow(b.min).grow(b.max)
+    }
+
+    pub fn pad(&self, r: f64) -> Self {
+        Self { min: self.min - r, max: self.max + r }
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /* @brief slab test, inv_dir is the componentwise reciprocal of the ray direction
+     */
+    pub fn hit(&self, ray: &Ray, inv_dir: Vec3, t_min: f64, t_max: f64) -> bool {
+        let (mut t0, mut t1) = (t_min, t_max);
+        for a in 0..3 {
+            let mut ta = (self.min[a] - ray.origin()[a]) * inv_dir[a];
+            let mut tb = (self.max[a] - ray.origin()[a]) * inv_dir[a];
+            if inv_dir[a] < 0.0 {
+                std::mem::swap(&mut ta, &mut tb);
+            }
+            t0 = t0.max(ta);
+            t1 = t1.min(tb);
+            if t1 < t0 {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+enum Node {
+    Interior { bounds: Aabb, left: usize, right: usize },
+    Leaf { bounds: Aabb, first: usize, count: usize },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Interior { bounds, .. } | Node::Leaf { bounds, .. } => bounds,
+        }
+    }
+}
+
+/* Bounding volume hierarchy over primitive indices. It knows nothing about the
+ *  primitives themselves, the owner hands in their boxes at build time and a
+ *  closure that intersects one primitive during traversal.
+ */
+pub struct Bvh {
+    nodes: Vec<Node>,
+    prims: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            prims: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            bvh.build(bounds, 0, bounds.len());
+        }
+        bvh
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |n| *n.bounds())
+    }
+
+    // median split along the widest axis of the centroids, returns the node index
+    fn build(&mut self, bounds: &[Aabb], first: usize, last: usize) -> usize {
+        let node_bounds = self.prims[first..last]
+            .iter()
+            .fold(Aabb::empty(), |b, &i| Aabb::union(b, bounds[i]));
+        let index = self.nodes.len();
+        let count = last - first;
+        if count <= MAX_LEAF_SIZE {
+            self.nodes.push(Node::Leaf { bounds: node_bounds, first, count });
+            return index;
+        }
+
+        let centroids = self.prims[first..last]
+            .iter()
+            .fold(Aabb::empty(), |b, &i| b.grow(bounds[i].centroid()));
+        let axis = widest_axis(centroids.extent());
+        let mid = first + count / 2;
+        self.prims[first..last].select_nth_unstable_by(count / 2, |&a, &b| {
+            bounds[a].centroid()[axis].total_cmp(&bounds[b].centroid()[axis])
+        });
+
+        // reserve the slot, children are filled in once they exist
+        self.nodes.push(Node::Leaf { bounds: node_bounds, first, count: 0 });
+        let left = self.build(bounds, first, mid);
+        let right = self.build(bounds, mid, last);
+        self.nodes[index] = Node::Interior { bounds: node_bounds, left, right };
+        index
+    }
+
+    /* @brief Walks the tree front to back. `test` is given a primitive index and the
+     *  closest distance so far and returns the distance of a closer hit, if any.
+     */
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, mut test: impl FnMut(usize, f64) -> Option<f64>) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let dir = ray.direction();
+        let inv_dir = Vec3::new(1.0 / dir.x(), 1.0 / dir.y(), 1.0 / dir.z());
+        let mut closest_so_far = t_max;
+        let mut hit_any = false;
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if !node.bounds().hit(ray, inv_dir, t_min, closest_so_far) {
+                continue;
+            }
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    for &prim in &self.prims[first..first + count] {
+                        if let Some(t) = test(prim, closest_so_far) {
+                            hit_any = true;
+                            closest_so_far = t;
+                        }
+                    }
+                }
+                Node::Interior { left, right, .. } => {
+                    // visit the child on the near side of the ray first
+                    let d = self.nodes[right].bounds().centroid() - self.nodes[left].bounds().centroid();
+                    let axis = widest_axis(d);
+                    if dir[axis] * d[axis] < 0.0 {
+                        stack.push(left);
+                        stack.push(right);
+                    } else {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                }
+            }
+        }
+        hit_any
+    }
+}
+
+fn widest_axis(d: Vec3) -> usize {
+    if d.x().abs() > d.y().abs() && d.x().abs() > d.z().abs() {
+        0
+    } else if d.y().abs() > d.z().abs() {
+        1
+    } else {
+        2
+    }
+}
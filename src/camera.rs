@@ -1,8 +1,43 @@
 use crate::vec3::{ Point3, Vec3 };
 use crate::ray::Ray;
-use crate::constants::DegToRad;
+use crate::constants::{pi, DegToRad, MM};
+use crate::aperture::Aperture;
+use crate::hit::{HitRecord, Hittable};
 
+/* Anything the render loop can pull primary rays from. s and t run 0..1 from the
+ *  left and from the bottom of the image.
+ */
+pub trait View {
+    // None where the projection has no picture, e.g. outside a fisheye's circle
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray>;
+
+    // image width over height the projection is laid out for
+    fn aspect_ratio(&self) -> f64;
+
+    // what the light reaching the image is scaled by on the way out
+    fn exposure(&self) -> f64 {
+        1.0
+    }
+}
+
+// u to the right, v up and w back toward the viewer, as in Camera::new
+pub(crate) fn frame(lookfrom: Point3, lookat: Point3, vup: Vec3) -> (Vec3, Vec3, Vec3) {
+    let w = Vec3::unit_vector(lookfrom - lookat);
+    let u = Vec3::unit_vector(Vec3::cross(vup, w));
+    (u, Vec3::cross(w, u), w)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    // thin lens, rays spread out from the lens
+    Perspective,
+    // parallel rays along -w from the whole view rectangle
+    Orthographic,
+}
+
+#[derive(Clone)]
 pub struct Camera {
+    pub projection: Projection,
     pub aspect_ratio: f64,
     pub viewport_height: f64,
     pub viewport_width: f64,
@@ -15,6 +50,15 @@ pub struct Camera {
     pub aperture: f64,
     pub lens_radius: f64,
     pub lower_left_corner: Point3,
+    // the shape of the lens opening, round unless set
+    pub aperture_shape: Aperture,
+    // how much the lens barrel clips the aperture toward the corners of the frame
+    pub cats_eye: f64,
+    // scale on the output, 1 unless set up from photographic settings
+    pub exposure: f64,
+    // the plane that is sharp, square on to w unless the lens is tilted
+    pub focus_point: Point3,
+    pub focus_normal: Vec3,
 }
 
 impl Camera {
@@ -38,6 +82,7 @@ impl Camera {
         let lens_radius = aperture/2.0;
 
         Self {
+            projection: Projection::Perspective,
             aspect_ratio,
             viewport_height,
             viewport_width,
@@ -50,13 +95,597 @@ impl Camera {
             aperture,
             lens_radius,
             lower_left_corner,
+            aperture_shape: Aperture::Circle,
+            cats_eye: 0.0,
+            exposure: 1.0,
+            focus_point: origin - focus_dist*w,
+            focus_normal: w,
+        }
+    }
+
+    /* @brief The camera a photographer would describe. The field of view comes from
+     *  the sensor height over the distance the lens sits from it when focused, the
+     *  aperture from the focal length over the f-number, and the exposure from the
+     *  f-number, shutter and ISO.
+     */
+    pub fn physical(lookfrom: Point3, lookat: Point3, vup: Vec3, settings: &CameraSettings) -> Self {
+        let CameraSettings { focal_length, sensor, f_number, focus_dist, .. } = *settings;
+        // thin lens equation, the lens moves out from the film to focus closer
+        let image_dist = focal_length * focus_dist / (focus_dist - focal_length * MM).max(1e-9);
+        let vfov = 2.0 * (sensor.height / (2.0 * image_dist)).atan().to_degrees();
+        let aperture = focal_length / f_number * MM;
+        let mut camera = Camera::new(lookfrom, lookat, vup, vfov, sensor.aspect_ratio(), aperture, focus_dist);
+        camera.exposure = settings.exposure();
+        camera
+    }
+
+    /* @brief A parallel projection of a view_width by view_height rectangle centred on
+     *  lookfrom, for technical drawings. Nothing is out of focus.
+     */
+    pub fn orthographic(
+        lookfrom: Point3, lookat: Point3, vup: Vec3, view_width: f64, view_height: f64) -> Self {
+        let w = Vec3::unit_vector(lookfrom - lookat);
+        let u = Vec3::unit_vector(Vec3::cross(vup, w));
+        let v = Vec3::cross(w, u);
+
+        let origin = lookfrom;
+        let horizontal = view_width * u;
+        let vertical = view_height * v;
+        // the corner of the view rectangle itself, rays leave it along -w
+        let lower_left_corner = origin - horizontal/2.0 - vertical/2.0;
+
+        Self {
+            projection: Projection::Orthographic,
+            aspect_ratio: view_width / view_height,
+            viewport_height: view_height,
+            viewport_width: view_width,
+            origin,
+            horizontal,
+            vertical,
+            u,
+            v,
+            w,
+            aperture: 0.0,
+            lens_radius: 0.0,
+            lower_left_corner,
+            aperture_shape: Aperture::Circle,
+            cats_eye: 0.0,
+            exposure: 1.0,
+            focus_point: origin,
+            focus_normal: w,
         }
     }
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::rand_in_unit_disk();
+
+    // bladed irises and masks, see Aperture
+    pub fn with_aperture(mut self, shape: Aperture) -> Self {
+        self.aperture_shape = shape;
+        self
+    }
+
+    /* @brief Optical vignetting. Off axis the lens barrel hides part of the aperture,
+     *  leaving the out of focus highlights toward the corners cat's eye shaped and the
+     *  corners darker. At 1 the barrel's edge reaches the middle of the aperture at the
+     *  corners of the frame.
+     */
+    pub fn with_cats_eye(mut self, strength: f64) -> Self {
+        self.cats_eye = strength.max(0.0);
+        self
+    }
+
+    /* @brief Shifts the lens across the frame by fractions of the frame's width and
+     *  height, for keeping verticals straight with the camera level and the top of a
+     *  building in the picture. The focus plane stays where it was.
+     */
+    pub fn with_shift(mut self, x: f64, y: f64) -> Self {
+        self.lower_left_corner += x * self.horizontal + y * self.vertical;
+        self
+    }
+
+    /* @brief Tilts the focus plane about the centre of focus, per Scheimpflug, in
+     *  degrees. A positive tilt takes the top of the plane further away, laying it
+     *  down along the ground, and a positive swing takes the right side further away.
+     */
+    pub fn with_tilt(mut self, tilt: f64, swing: f64) -> Self {
+        let normal = self.w + tilt.deg_to_rad().tan() * self.v + swing.deg_to_rad().tan() * self.u;
+        self.focus_normal = Vec3::unit_vector(normal);
+        self
+    }
+
+    // distance to the focus plane along the view direction
+    pub fn focus_dist(&self) -> f64 {
+        Vec3::dot(self.origin - self.focus_point, self.w)
+    }
+
+    /* @brief Autofocus. Focuses on whatever the ray through the middle of the lens at
+     *  s, t hits first, (0.5, 0.5) for the centre of the frame, keeping any tilt.
+     *  Left as it was if the ray hits nothing.
+     */
+    pub fn focus_on(mut self, world: &dyn Hittable, s: f64, t: f64) -> Self {
+        if self.projection == Projection::Orthographic {
+            return self;
+        }
+        let through = self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin;
+        let mut record = HitRecord::new();
+        if !world.hit(&Ray::new(self.origin, through), 0.001, f64::INFINITY, &mut record) {
+            return self;
+        }
+        // the image plane moves with the focus so the frame stays the same
+        let scale = Vec3::dot(self.origin - record.point(), self.w) / self.focus_dist();
+        self.horizontal *= scale;
+        self.vertical *= scale;
+        self.lower_left_corner = self.origin + scale * (self.lower_left_corner - self.origin);
+        self.focus_point = record.point();
+        self
+    }
+
+    // None for the samples the lens barrel blocks
+    pub fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        if self.projection == Projection::Orthographic {
+            return Some(Ray::new(self.lower_left_corner + s * self.horizontal + t * self.vertical, -self.w));
+        }
+        let lens = self.aperture_shape.sample(&mut rand::thread_rng());
+        if self.cats_eye > 0.0 {
+            // a second, equal circle slid out toward the corner the pixel is in
+            let (x, y) = ((2.0 * s - 1.0) * self.aspect_ratio, 2.0 * t - 1.0);
+            let shift = self.cats_eye / (self.aspect_ratio * self.aspect_ratio + 1.0).sqrt();
+            if (lens - shift * Vec3::new(x, y, 0.0)).length() > 1.0 {
+                return None;
+            }
+        }
+        let rd = self.lens_radius * lens;
         let offset = self.u * rd.x() + self.v * rd.y();
-            Ray::new(
-                self.origin + offset, 
-                self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset)
+        // where the ray through the middle of the lens meets the focus plane, which is on
+        //  the image plane unless the lens is tilted
+        let through = self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin;
+        let along = Vec3::dot(self.focus_point - self.origin, self.focus_normal) / Vec3::dot(through, self.focus_normal);
+        if along > 0.0 && along.is_finite() {
+            Some(Ray::new(self.origin + offset, along * through - offset))
+        } else {
+            // a tilted plane that never comes round to meet it, sharp at infinity
+            Some(Ray::new(self.origin + offset, through))
         }
+    }
+}
+
+impl View for Camera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        Camera::get_ray(self, s, t)
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        self.aspect_ratio
+    }
+
+    fn exposure(&self) -> f64 {
+        self.exposure
+    }
+}
+
+// the imaging area of a camera body in mm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sensor {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Sensor {
+    pub const FULL_FRAME: Sensor = Sensor { width: 36.0, height: 24.0 };
+    pub const APS_C: Sensor = Sensor { width: 23.6, height: 15.6 };
+    pub const MICRO_FOUR_THIRDS: Sensor = Sensor { width: 17.3, height: 13.0 };
+    pub const SUPER_35: Sensor = Sensor { width: 24.89, height: 18.66 };
+    pub const MEDIUM_FORMAT: Sensor = Sensor { width: 43.8, height: 32.9 };
+
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width / self.height
+    }
+}
+
+/* Settings for Camera::physical. Lengths are in mm apart from focus_dist, which is in
+ *  scene units as metres, and the shutter is in seconds.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraSettings {
+    pub focal_length: f64,
+    pub sensor: Sensor,
+    pub f_number: f64,
+    pub focus_dist: f64,
+    pub shutter: f64,
+    pub iso: f64,
+}
+
+impl Default for CameraSettings {
+    // a 50mm on full frame at sunny sixteen
+    fn default() -> Self {
+        Self {
+            focal_length: 50.0,
+            sensor: Sensor::FULL_FRAME,
+            f_number: 16.0,
+            focus_dist: 10.0,
+            shutter: 1.0 / 100.0,
+            iso: 100.0,
+        }
+    }
+}
+
+impl CameraSettings {
+    /* @brief The scale on the output. A radiance of 1 is the sky of a sunlit scene,
+     *  so sunny sixteen (f/16 at a shutter of 1/ISO) leaves it as it is, and every stop
+     *  either way doubles or halves it.
+     */
+    pub fn exposure(&self) -> f64 {
+        // the f-number squared over shutter times ISO, which is 1 at sunny sixteen
+        let sunny_sixteen = 16.0 * 16.0;
+        self.shutter * self.iso / (self.f_number * self.f_number) * sunny_sixteen
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FisheyeMapping {
+    // distance from the centre proportional to the angle off axis
+    Equidistant,
+    // equal areas of the image cover equal solid angles
+    Equisolid,
+}
+
+/* A fisheye whose image circle fills the height of the frame, fov being the full
+ *  angle across the circle in degrees (180 or more for a full hemisphere)
+ */
+pub struct Fisheye {
+    origin: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    half_fov: f64,
+    aspect_ratio: f64,
+    mapping: FisheyeMapping,
+}
+
+impl Fisheye {
+    pub fn new(
+        lookfrom: Point3, lookat: Point3, vup: Vec3, fov: f64, aspect_ratio: f64,
+        mapping: FisheyeMapping) -> Self {
+        let (u, v, w) = frame(lookfrom, lookat, vup);
+        Self { origin: lookfrom, u, v, w, half_fov: fov.deg_to_rad() / 2.0, aspect_ratio, mapping }
+    }
+}
+
+impl View for Fisheye {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let (x, y) = ((2.0 * s - 1.0) * self.aspect_ratio, 2.0 * t - 1.0);
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.half_fov,
+            FisheyeMapping::Equisolid => 2.0 * (r * (self.half_fov / 2.0).sin()).asin(),
+        };
+        let (cx, cy) = if r > 0.0 { (x / r, y / r) } else { (0.0, 0.0) };
+        let dir = theta.cos() * -self.w + theta.sin() * (cx * self.u + cy * self.v);
+        Some(Ray::new(self.origin, dir))
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        self.aspect_ratio
+    }
+}
+
+/* Full 360 by 180 degree panorama in latitude and longitude, lookat in the middle
+ *  of the image. The usual 2:1 layout for VR and environment maps.
+ */
+pub struct Equirectangular {
+    origin: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    // to the right of every viewing direction, one eye of an omni-directional pair
+    eye_offset: f64,
+}
+
+impl Equirectangular {
+    pub fn new(lookfrom: Point3, lookat: Point3, vup: Vec3) -> Self {
+        let (u, v, w) = frame(lookfrom, lookat, vup);
+        Self { origin: lookfrom, u, v, w, eye_offset: 0.0 }
+    }
+}
+
+impl View for Equirectangular {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let phi = (s - 0.5) * 2.0 * pi;
+        let theta = (t - 0.5) * pi;
+        let dir = theta.cos() * (phi.sin() * self.u - phi.cos() * self.w) + theta.sin() * self.v;
+        // the eyes sit on a circle, turning with the head as it looks around
+        let right = phi.cos() * self.u + phi.sin() * self.w;
+        Some(Ray::new(self.origin + self.eye_offset * right, dir))
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        2.0
+    }
+}
+
+/* The six 90 degree faces around a point, laid out left to right as +x, -x, +y, -y,
+ *  +z and -z of the world axes, as reflection probes want. Each face is seen from
+ *  the inside with +y up, and the y faces have -z and +z up respectively.
+ */
+pub struct CubeMap {
+    origin: Point3,
+}
+
+impl CubeMap {
+    pub fn new(origin: Point3) -> Self {
+        Self { origin }
+    }
+}
+
+impl View for CubeMap {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let face = ((s * 6.0) as usize).min(5);
+        let x = 2.0 * (s * 6.0 - face as f64) - 1.0;
+        let y = 2.0 * t - 1.0;
+        // forward plus x to the right and y up, right being forward cross up
+        let dir = match face {
+            0 => Vec3::new(1.0, y, x),
+            1 => Vec3::new(-1.0, y, -x),
+            2 => Vec3::new(-x, 1.0, -y),
+            3 => Vec3::new(-x, -1.0, y),
+            4 => Vec3::new(-x, y, 1.0),
+            _ => Vec3::new(x, y, -1.0),
+        };
+        Some(Ray::new(self.origin, dir))
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        6.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StereoMode {
+    // both eyes look straight ahead, nothing sits at the screen but infinity
+    Parallel,
+    // parallel eyes with their frusta shifted to share one window at the convergence
+    //  distance, without the keystoning of toeing in
+    OffAxis,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StereoLayout {
+    // left eye on the left half
+    SideBySide,
+    // left eye on the top half
+    TopBottom,
+}
+
+/* Two views packed into one image for headsets and 3D displays, each eye getting
+ *  its half of the frame at the eye's own aspect ratio.
+ */
+pub struct Stereo {
+    left: Box<dyn View + Sync + Send>,
+    right: Box<dyn View + Sync + Send>,
+    layout: StereoLayout,
+}
+
+impl Stereo {
+    // any pair of views, which should share an aspect ratio
+    pub fn new(left: Box<dyn View + Sync + Send>, right: Box<dyn View + Sync + Send>, layout: StereoLayout) -> Self {
+        Self { left, right, layout }
+    }
+
+    /* @brief The eyes either side of a perspective camera, interocular apart along its
+     *  u. Objects at the convergence distance appear at the depth of the screen.
+     */
+    pub fn from_camera(
+        cam: &Camera, interocular: f64, convergence: f64, mode: StereoMode, layout: StereoLayout) -> Self {
+        let eye = |side: f64| {
+            let offset = side * interocular / 2.0 * cam.u;
+            let mut eye = cam.clone();
+            eye.origin = cam.origin + offset;
+            eye.lower_left_corner = cam.lower_left_corner + offset;
+            if mode == StereoMode::OffAxis {
+                // back toward the middle by however much of the offset is left at the
+                //  focus plane, so the centre rays cross at the convergence distance
+                let focus_dist = cam.horizontal.length() / cam.viewport_width;
+                eye.lower_left_corner -= offset * (focus_dist / convergence);
+            }
+            Box::new(eye)
+        };
+        Self::new(eye(-1.0), eye(1.0), layout)
+    }
+
+    /* @brief Omni-directional stereo panorama. Every column of each eye's panorama is
+     *  seen from where that eye would be with the head turned to face it.
+     */
+    pub fn ods(lookfrom: Point3, lookat: Point3, vup: Vec3, interocular: f64, layout: StereoLayout) -> Self {
+        let eye = |side: f64| {
+            let mut eye = Equirectangular::new(lookfrom, lookat, vup);
+            eye.eye_offset = side * interocular / 2.0;
+            Box::new(eye)
+        };
+        Self::new(eye(-1.0), eye(1.0), layout)
+    }
+}
+
+impl View for Stereo {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        match self.layout {
+            StereoLayout::SideBySide if s < 0.5 => self.left.get_ray(2.0 * s, t),
+            StereoLayout::SideBySide => self.right.get_ray(2.0 * s - 1.0, t),
+            StereoLayout::TopBottom if t >= 0.5 => self.left.get_ray(s, 2.0 * t - 1.0),
+            StereoLayout::TopBottom => self.right.get_ray(s, 2.0 * t),
+        }
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        match self.layout {
+            StereoLayout::SideBySide => 2.0 * self.left.aspect_ratio(),
+            StereoLayout::TopBottom => self.left.aspect_ratio() / 2.0,
+        }
+    }
+
+    fn exposure(&self) -> f64 {
+        self.left.exposure()
+    }
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{Camera, CameraSettings, CubeMap, Equirectangular, Fisheye, FisheyeMapping, Stereo, StereoLayout, StereoMode, View};
+    use crate::vec3::{Point3, Vec3};
+
+    fn direction(view: &dyn View, s: f64, t: f64) -> Vec3 {
+        Vec3::unit_vector(view.get_ray(s, t).unwrap().direction())
+    }
+
+    #[test]
+    fn projections_point_the_right_way() {
+        let (from, at, up) = (Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+
+        let pano = Equirectangular::new(from, at, up);
+        assert!((direction(&pano, 0.5, 0.5) - Vec3::new(0.0, 0.0, -1.0)).near_zero());
+        assert!((direction(&pano, 0.75, 0.5) - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!((direction(&pano, 0.5, 1.0) - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+
+        for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+            let fisheye = Fisheye::new(from, at, up, 180.0, 1.5, mapping);
+            assert!((direction(&fisheye, 0.5, 0.5) - Vec3::new(0.0, 0.0, -1.0)).near_zero());
+            // the top of the circle is 90 degrees off axis
+            assert!((direction(&fisheye, 0.5, 1.0) - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+            assert!(fisheye.get_ray(0.0, 0.5).is_none());
+        }
+
+        let cube = CubeMap::new(from);
+        let axes = [(1.0, 0.0, 0.0), (-1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, -1.0, 0.0), (0.0, 0.0, 1.0), (0.0, 0.0, -1.0)];
+        for (face, (x, y, z)) in axes.into_iter().enumerate() {
+            let centre = direction(&cube, (face as f64 + 0.5) / 6.0, 0.5);
+            assert!((centre - Vec3::new(x, y, z)).near_zero());
+            // seen from inside, the right edge is forward cross up
+            let right = direction(&cube, (face as f64 + 1.0) / 6.0 - 1e-9, 0.5);
+            let up = direction(&cube, (face as f64 + 0.5) / 6.0, 1.0);
+            let up = Vec3::unit_vector(up - Vec3::dot(up, centre) * centre);
+            let expected = Vec3::unit_vector(centre + Vec3::cross(centre, up));
+            assert!((right - expected).length() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn cats_eye_vignettes_off_axis() {
+        let (from, at, up) = (Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        // the barrel only gets in the way off axis
+        let lens = Camera::new(from, at, up, 40.0, 1.5, 0.5, 3.0).with_cats_eye(1.0);
+        let blocked = |s, t| (0..1000).filter(|_| lens.get_ray(s, t).is_none()).count();
+        assert_eq!(blocked(0.5, 0.5), 0);
+        assert!((300..700).contains(&blocked(1.0, 1.0)));
+    }
+
+    #[test]
+    fn physical_settings_set_the_view_and_exposure() {
+        let (from, at, up) = (Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        // a 50mm on full frame sees 27 degrees top to bottom, a little less up close
+        let settings = CameraSettings { focus_dist: 1e6, ..CameraSettings::default() };
+        let far = Camera::physical(from, at, up, &settings);
+        assert!((far.viewport_height - 2.0 * 0.24f64.atan().tan()).abs() < 1e-6);
+        assert!((far.aperture - 0.050 / 16.0).abs() < 1e-12 && far.aspect_ratio == 1.5);
+        assert!((far.exposure() - 1.0).abs() < 1e-12);
+        let near = Camera::physical(from, at, up, &CameraSettings { focus_dist: 0.5, f_number: 8.0, ..settings });
+        assert!(near.viewport_height < far.viewport_height);
+        assert!((near.exposure() - 4.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn orthographic_rays_run_parallel() {
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        // a 4 by 2 view centred on lookfrom, every ray heading down -z
+        let ortho = Camera::orthographic(Point3::new(1.0, 2.0, 3.0), Point3::new(1.0, 2.0, -1.0), up, 4.0, 2.0);
+        assert_eq!(ortho.aspect_ratio(), 2.0);
+        for (s, t, origin) in [(0.0, 0.0, (-1.0, 1.0)), (1.0, 1.0, (3.0, 3.0)), (0.5, 0.5, (1.0, 2.0)), (0.25, 0.75, (0.0, 2.5))] {
+            let ray = ortho.get_ray(s, t).unwrap();
+            assert!((ray.origin() - Point3::new(origin.0, origin.1, 3.0)).length() < 1e-12);
+            assert!((direction(&ortho, s, t) - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-12);
+        }
+
+        // looking down +x the right of the view is +z
+        let side = Camera::orthographic(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), up, 1.0, 3.0);
+        let (a, b) = (side.get_ray(0.0, 0.5).unwrap(), side.get_ray(1.0, 0.0).unwrap());
+        assert!((a.origin() - Point3::new(0.0, 0.0, -0.5)).length() < 1e-12);
+        assert!((b.origin() - Point3::new(0.0, -1.5, 0.5)).length() < 1e-12);
+        assert!((direction(&side, 0.0, 0.5) - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((direction(&side, 1.0, 0.0) - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn stereo_eyes_converge() {
+        let (from, at, up) = (Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        // pinhole, so the rays are exact
+        let cam = Camera::new(from, at, up, 40.0, 1.5, 0.0, 3.0);
+
+        let pair = Stereo::from_camera(&cam, 0.2, 5.0, StereoMode::OffAxis, StereoLayout::SideBySide);
+        assert!((pair.aspect_ratio() - 3.0).abs() < 1e-12);
+        let (left, right) = (pair.get_ray(0.25, 0.5).unwrap(), pair.get_ray(0.75, 0.5).unwrap());
+        assert!((left.origin() - Point3::new(-0.1, 0.0, 0.0)).near_zero());
+        assert!((right.origin() - Point3::new(0.1, 0.0, 0.0)).near_zero());
+        // the centre rays cross on the axis at the convergence distance
+        let meet = |ray: &crate::ray::Ray| ray.origin() + (-5.0 / ray.direction().z()) * ray.direction();
+        assert!((meet(&left) - Point3::new(0.0, 0.0, -5.0)).length() < 1e-9);
+        assert!((meet(&right) - Point3::new(0.0, 0.0, -5.0)).length() < 1e-9);
+
+        let pair = Stereo::from_camera(&cam, 0.2, 5.0, StereoMode::Parallel, StereoLayout::TopBottom);
+        assert!((pair.aspect_ratio() - 0.75).abs() < 1e-12);
+        let (left, right) = (direction(&pair, 0.5, 0.75), direction(&pair, 0.5, 0.25));
+        assert!((left - Vec3::new(0.0, 0.0, -1.0)).near_zero() && (right - left).near_zero());
+
+        // looking to the right, the left eye is in front of the centre
+        let ods = Stereo::ods(from, at, up, 0.2, StereoLayout::TopBottom);
+        let left = ods.get_ray(0.75, 0.75).unwrap();
+        assert!((left.origin() - Point3::new(0.0, 0.0, -0.1)).length() < 1e-9);
+        assert!((Vec3::unit_vector(left.direction()) - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn tilt_shift_moves_the_focus_plane() {
+        let (from, at, up) = (Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        let level = Camera::new(from, at, up, 40.0, 1.5, 0.0, 5.0);
+        let shifted = level.clone().with_shift(0.0, 0.25);
+        // the middle of the frame looks up, but the image plane still faces -z
+        assert!(direction(&shifted, 0.5, 0.5).y() > 0.0);
+        assert!((shifted.get_ray(0.5, 0.5).unwrap().direction().z() + 5.0).abs() < 1e-9);
+
+        // whatever part of the lens they pass through, rays for a pixel meet on the plane
+        let tilted = Camera::new(from, at, up, 40.0, 1.5, 1.0, 5.0).with_tilt(30.0, 0.0);
+        for t in [0.1, 0.9] {
+            let meet = |ray: crate::ray::Ray| {
+                let p = ray.origin() + ray.direction();
+                assert!(Vec3::dot(p - tilted.focus_point, tilted.focus_normal).abs() < 1e-9);
+                p
+            };
+            let first = meet(tilted.get_ray(0.5, t).unwrap());
+            for _ in 0..10 {
+                assert!((meet(tilted.get_ray(0.5, t).unwrap()) - first).length() < 1e-9);
+            }
+            // nearer at the bottom of the frame, further at the top
+            assert_eq!(first.z() < -5.0, t > 0.5);
+        }
+    }
+
+    #[test]
+    fn autofocus_finds_the_subject() {
+        use crate::hit::{HittableList, Lambertian, Sphere};
+        use crate::vec3::Colour;
+        use std::sync::Arc;
+
+        let mut world = HittableList::new();
+        let material = Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5)));
+        world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, -7.0), 1.0, material)));
+        let (from, at, up) = (Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        let cam = Camera::new(from, at, up, 40.0, 1.5, 0.0, 10.0);
+        let before = direction(&cam, 0.2, 0.7);
+
+        let focused = cam.clone().focus_on(&world, 0.5, 0.5);
+        assert!((focused.focus_dist() - 6.0).abs() < 1e-9);
+        // the framing doesn't change
+        assert!((direction(&focused, 0.2, 0.7) - before).length() < 1e-12);
+        // nothing at the edge of the frame, so focus stays put
+        assert!((cam.focus_on(&world, 0.0, 0.0).focus_dist() - 10.0).abs() < 1e-9);
+    }
 }
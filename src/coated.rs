@@ -0,0 +1,172 @@
+use crate::fresnel::fr_dielectric;
+use crate::hit::{HitRecord, Material};
+use crate::microfacet::TrowbridgeReitz;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vec3::{Colour, Vec3};
+use rand::Rng;
+use std::sync::Arc;
+
+/* Car paint and lacquer. A clear dielectric coat over any other material, taken as
+ *  infinitely thin in position but not in absorption. Light either reflects off the
+ *  coat or refracts in and bounces between the base and the underside of the coat,
+ *  fresnel deciding at every crossing, until it finds its way out or is absorbed.
+ */
+pub struct Coated {
+    base: Arc<dyn Material + Sync + Send>,
+    ir: f64,
+    distrib: TrowbridgeReitz,
+    // per unit length inside the coat, over the coat's thickness
+    absorption: Colour,
+    thickness: f64,
+}
+
+impl Coated {
+    // walks longer than this between the coat and the base are dropped
+    const MAX_BOUNCES: usize = 32;
+
+    pub fn new(base: Arc<dyn Material + Sync + Send>, ir: f64, roughness: f64) -> Self {
+        Self {
+            base,
+            ir,
+            distrib: TrowbridgeReitz::new(roughness, roughness),
+            absorption: Colour::new_z(),
+            thickness: 0.0,
+        }
+    }
+
+    // a tinted coat, light loses exp(-absorption thickness / cos) on each pass
+    pub fn with_absorption(mut self, absorption: Colour, thickness: f64) -> Self {
+        self.absorption = absorption;
+        self.thickness = thickness;
+        self
+    }
+
+    fn pass(&self, w: Vec3) -> Colour {
+        let d = self.thickness / w.z().abs().max(1e-6);
+        Colour::new((-self.absorption.x() * d).exp(), (-self.absorption.y() * d).exp(), (-self.absorption.z() * d).exp())
+    }
+
+    /* @brief Crosses the coat from w (pointing away on the side it arrives from) with
+     *  eta the index beyond over the index before. Gives the new direction, whether it
+     *  got through and the masking weight.
+     */
+    fn cross(&self, w: Vec3, eta: f64, rng: &mut impl Rng) -> Option<(Vec3, bool, f64)> {
+        // the microsurface looks the same from below with its normals flipped
+        let up = if w.z() > 0.0 { 1.0 } else { -1.0 };
+        let wu = Vec3::new(w.x(), w.y(), up * w.z());
+        let wm = if self.distrib.effectively_smooth() {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            self.distrib.sample_wm(wu, rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0))
+        };
+        let reflect = fr_dielectric(Vec3::dot(wu, wm), eta) > rng.gen_range(0.0..1.0);
+        let wi = if reflect { Vec3::reflect(-wu, wm) } else { Vec3::refract(-wu, wm, 1.0 / eta) };
+        if (wi.z() > 0.0) != reflect {
+            return None;
+        }
+        let weight = if self.distrib.effectively_smooth() {
+            1.0
+        } else {
+            self.distrib.g(wu, wi) / self.distrib.g1(wu)
+        };
+        Some((Vec3::new(wi.x(), wi.y(), up * wi.z()), !reflect, weight))
+    }
+
+    // the whole walk in the shading frame, the weight and direction it leaves in
+    fn walk(&self, wo: Vec3, ray: &Ray, record: &HitRecord, frame: &Onb, rng: &mut impl Rng) -> Option<(Colour, Vec3, Ray)> {
+        let (mut w, through, weight) = self.cross(wo, self.ir, rng)?;
+        let mut total = Colour::new(weight, weight, weight);
+        if !through {
+            return Some((total, w, Ray::default()));
+        }
+        for _ in 0..Self::MAX_BOUNCES {
+            // down through the coat to the base and back up
+            total *= self.pass(w);
+            let mut incoming = Ray::new(record.point() - frame.local(w), frame.local(w));
+            if let Some(wavelengths) = ray.wavelengths() {
+                incoming = incoming.with_wavelengths(wavelengths);
+            }
+            let mut attenuation = Colour::new_z();
+            let mut scattered = Ray::default();
+            if !self.base.scatter(incoming, record, &mut attenuation, &mut scattered) {
+                return None;
+            }
+            let up = frame.to_local(Vec3::unit_vector(scattered.direction()));
+            if up.z() <= 0.0 {
+                return None;
+            }
+            total *= attenuation * self.pass(up);
+
+            // and out through the coat from underneath, or back down again
+            let (next, through, weight) = self.cross(-up, 1.0 / self.ir, rng)?;
+            total *= weight;
+            if through {
+                return Some((total, next, scattered));
+            }
+            w = next;
+        }
+        None
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, ray: Ray, record: &HitRecord, attenuation: &mut Colour, scattered: &mut Ray) -> bool {
+        let frame = Onb::from_w(record.norm());
+        let wo = frame.to_local(-Vec3::unit_vector(ray.direction()));
+        if wo.z() <= 0.0 {
+            return false;
+        }
+        match self.walk(wo, &ray, record, &frame, &mut rand::thread_rng()) {
+            Some((weight, wi, from_base)) => {
+                *attenuation = weight;
+                *scattered = Ray::new(record.point(), frame.local(wi));
+                // a base that narrowed the wavelengths down has its say
+                if let Some(wavelengths) = from_base.wavelengths() {
+                    *scattered = std::mem::take(scattered).with_wavelengths(wavelengths);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::Coated;
+    use crate::hit::{HitRecord, Lambertian, Material};
+    use crate::ray::Ray;
+    use crate::vec3::{Colour, Point3, Vec3};
+    use std::sync::Arc;
+
+    #[test]
+    fn clear_coat_keeps_energy() {
+        let mut record = HitRecord::new();
+        record.norm = Vec3::new(0.0, 0.0, 1.0);
+        record.front_face = true;
+        let white = Arc::new(Lambertian::new(Colour::new(1.0, 1.0, 1.0)));
+        let clear = Coated::new(white.clone(), 1.5, 0.0);
+        let tinted = Coated::new(white, 1.5, 0.0).with_absorption(Colour::new(1.0, 2.0, 4.0), 0.1);
+
+        let n = 20_000;
+        let (mut kept, mut tinted_mean) = (0, Colour::new_z());
+        for _ in 0..n {
+            let ray = Ray::new(Point3::new(-1.0, 0.0, 1.0), Vec3::new(1.0, 0.0, -1.0));
+            let (mut attenuation, mut scattered) = (Colour::new_z(), Ray::default());
+            if clear.scatter(ray, &record, &mut attenuation, &mut scattered) {
+                // every path that leaves carries all of its energy with it
+                assert!((attenuation - Colour::new(1.0, 1.0, 1.0)).near_zero());
+                assert!(scattered.direction().z() > 0.0);
+                kept += 1;
+            }
+            let ray = Ray::new(Point3::new(-1.0, 0.0, 1.0), Vec3::new(1.0, 0.0, -1.0));
+            if tinted.scatter(ray, &record, &mut attenuation, &mut scattered) {
+                tinted_mean += attenuation / n as f64;
+            }
+        }
+        assert!(kept > n * 999 / 1000, "{kept}");
+        assert!(tinted_mean.x() > tinted_mean.y() && tinted_mean.y() > tinted_mean.z());
+    }
+}
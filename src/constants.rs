@@ -1,8 +1,11 @@
 pub use std::f64::consts::PI as pi;
-pub use std::f64::INFINITY as inf;
+#[allow(non_upper_case_globals)]
+pub const inf: f64 = f64::INFINITY;
+// scene units are metres wherever a physical size in mm comes in
+pub const MM: f64 = 0.001;
 
 pub fn deg_to_rad(degrees: f64) -> f64 {
-    return degrees * pi / 180.0;
+    degrees * pi / 180.0
 }
 
 pub trait DegToRad {
@@ -0,0 +1,297 @@
+use crate::bvh::{Aabb, Bvh};
+use crate::hit::{Collide, HitRecord, Hittable, Intersects, Material};
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurveMode {
+    // a strip that always turns to face the incoming ray, good enough for grass and distant hair
+    Flat,
+    // shaded and offset as if it were a tube of the given width
+    Cylinder,
+}
+
+/* A cubic bezier strand whose width is interpolated linearly from one end to the other
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Curve {
+    cp: [Point3; 4],
+    width: (f64, f64),
+    mode: CurveMode,
+}
+
+struct CurveHit {
+    t: f64,
+    u: f64,
+    // offset across the strand, -1 on one edge to 1 on the other
+    h: f64,
+}
+
+impl Curve {
+    pub fn new(cp: [Point3; 4], width: (f64, f64), mode: CurveMode) -> Self {
+        Self { cp, width, mode }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(&self.cp).pad(0.5 * self.width.0.max(self.width.1))
+    }
+
+    pub fn point(&self, u: f64) -> Point3 {
+        eval_bezier(&self.cp, u)
+    }
+
+    pub fn tangent(&self, u: f64) -> Vec3 {
+        bezier_derivative(&self.cp, u)
+    }
+
+    /* @brief Works in a frame where the ray runs down +z from the origin, so a hit is
+     *  a point of the curve whose xy distance to the axis is under half its width.
+     *  The curve is split in halves until the pieces are close enough to straight.
+     */
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<CurveHit> {
+        let dir_len = ray.direction().length();
+        let dz = ray.direction() / dir_len;
+        let dx = if dz.x().abs() > dz.y().abs() {
+            Vec3::unit_vector(Vec3::new(-dz.z(), 0.0, dz.x()))
+        } else {
+            Vec3::unit_vector(Vec3::new(0.0, dz.z(), -dz.y()))
+        };
+        let dy = Vec3::cross(dz, dx);
+        let to_ray = |p: Point3| {
+            let d = p - ray.origin();
+            Vec3::new(Vec3::dot(d, dx), Vec3::dot(d, dy), Vec3::dot(d, dz))
+        };
+        let cp = self.cp.map(to_ray);
+
+        // subdivisions needed for the pieces to deviate less than a twentieth of the width
+        let mut l0: f64 = 0.0;
+        for i in 0..2 {
+            let d = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+            l0 = l0.max(d.x().abs().max(d.y().abs()).max(d.z().abs()));
+        }
+        let eps = self.width.0.max(self.width.1) / 20.0;
+        let depth = if l0 > 0.0 {
+            ((std::f64::consts::SQRT_2 * 6.0 * l0 / (8.0 * eps)).log2() / 2.0).clamp(0.0, 10.0) as usize
+        } else {
+            0
+        };
+
+        let mut closest = None;
+        self.recurse(&cp, 0.0, 1.0, depth, t_min * dir_len, t_max * dir_len, &mut closest);
+        closest.map(|(z, u, h)| CurveHit { t: z / dir_len, u, h })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn recurse(&self, cp: &[Vec3; 4], u0: f64, u1: f64, depth: usize, z_min: f64, mut z_max: f64,
+        closest: &mut Option<(f64, f64, f64)>) {
+        if let Some((z, _, _)) = closest {
+            z_max = *z;
+        }
+        let half_width = 0.5 * lerp(u0, self.width.0, self.width.1).max(lerp(u1, self.width.0, self.width.1));
+        let b = Aabb::from_points(cp).pad(half_width);
+        if b.min.x() > 0.0 || b.max.x() < 0.0 || b.min.y() > 0.0 || b.max.y() < 0.0
+            || b.max.z() < z_min || b.min.z() > z_max {
+            return;
+        }
+
+        if depth > 0 {
+            let (left, right) = split_bezier(cp);
+            let um = 0.5 * (u0 + u1);
+            self.recurse(&left, u0, um, depth - 1, z_min, z_max, closest);
+            self.recurse(&right, um, u1, depth - 1, z_min, z_max, closest);
+            return;
+        }
+
+        // the closest approach has to fall between the end caps of this piece
+        let edge = (cp[1].y() - cp[0].y()) * -cp[0].y() + cp[0].x() * (cp[0].x() - cp[1].x());
+        if edge < 0.0 {
+            return;
+        }
+        let edge = (cp[2].y() - cp[3].y()) * -cp[3].y() + cp[3].x() * (cp[3].x() - cp[2].x());
+        if edge < 0.0 {
+            return;
+        }
+
+        // treat the piece as a segment to find where along it the axis passes closest
+        let seg = Vec3::new(cp[3].x() - cp[0].x(), cp[3].y() - cp[0].y(), 0.0);
+        let denom = seg.length_squared();
+        if denom == 0.0 {
+            return;
+        }
+        let w = ((-cp[0].x() * seg.x() - cp[0].y() * seg.y()) / denom).clamp(0.0, 1.0);
+        let u = lerp(w, u0, u1);
+        let radius = 0.5 * lerp(u, self.width.0, self.width.1);
+        let pc = eval_bezier(cp, w);
+        let dist2 = pc.x() * pc.x() + pc.y() * pc.y();
+        if dist2 > radius * radius {
+            return;
+        }
+
+        // signed offset from the centre line, measured perpendicular to the curve
+        let d = bezier_derivative(cp, w);
+        let d_len = (d.x() * d.x() + d.y() * d.y()).sqrt();
+        let h = if d_len > 0.0 {
+            ((d.x() * -pc.y() + pc.x() * d.y()) / d_len / radius).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        let z = match self.mode {
+            CurveMode::Flat => pc.z(),
+            CurveMode::Cylinder => pc.z() - (radius * radius - dist2).sqrt(),
+        };
+        if z < z_min || z > z_max {
+            return;
+        }
+        *closest = Some((z, u, h));
+    }
+}
+
+/* A bundle of strands sharing one material, kept in their own BVH so a head of
+ *  hair or a field of grass is a single entry of the world list
+ */
+pub struct CurveSet {
+    curves: Vec<Curve>,
+    bvh: Bvh,
+    material: Arc<dyn Material + Sync + Send>,
+}
+
+impl CurveSet {
+    pub fn new(curves: Vec<Curve>, material: Arc<dyn Material + Sync + Send>) -> Self {
+        let bounds: Vec<Aabb> = curves.iter().map(|c| c.bounds()).collect();
+        let bvh = Bvh::new(&bounds);
+        Self { curves, bvh, material }
+    }
+
+    pub fn len(&self) -> usize {
+        self.curves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.curves.is_empty()
+    }
+}
+
+impl Hittable for CurveSet {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_record: &mut HitRecord) -> bool {
+        let mut closest: Option<(usize, CurveHit)> = None;
+        let hit_any = self.bvh.hit(ray, t_min, t_max, |i, t_hi| {
+            let hit = self.curves[i].intersect(ray, t_min, t_hi)?;
+            let t = hit.t;
+            closest = Some((i, hit));
+            Some(t)
+        });
+        let (i, hit) = match closest {
+            Some(c) if hit_any => c,
+            _ => return false,
+        };
+
+        let curve = &self.curves[i];
+        let dpdu = curve.tangent(hit.u);
+        let tangent = Vec3::unit_vector(dpdu);
+        // the ribbon faces back along the ray, across it runs dpdv
+        let to_eye = -Vec3::unit_vector(ray.direction());
+        let facing = Vec3::unit_vector(to_eye - Vec3::dot(to_eye, tangent) * tangent);
+        let across = Vec3::cross(tangent, facing);
+        let norm = match curve.mode {
+            CurveMode::Flat => facing,
+            CurveMode::Cylinder => {
+                let angle = hit.h.asin();
+                angle.cos() * facing + angle.sin() * across
+            }
+        };
+
+        hit_record.t = hit.t;
+        hit_record.point = ray.at(hit.t);
+        hit_record.set_face_norm(ray, norm);
+        hit_record.u = hit.u;
+        hit_record.v = 0.5 * (hit.h + 1.0);
+        hit_record.dpdu = dpdu;
+        hit_record.dpdv = lerp(hit.u, curve.width.0, curve.width.1) * across;
+        hit_record.material = Some(self.material.clone());
+        true
+    }
+}
+
+impl Intersects for CurveSet {
+    fn center(&self) -> Point3 {
+        self.bvh.bounds().centroid()
+    }
+
+    fn radius(&self) -> f64 {
+        0.5 * self.bvh.bounds().extent().length()
+    }
+}
+
+impl Collide for CurveSet {
+
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    (1.0 - t) * a + t * b
+}
+
+fn eval_bezier(cp: &[Vec3; 4], u: f64) -> Vec3 {
+    let a = [lerp3(u, cp[0], cp[1]), lerp3(u, cp[1], cp[2]), lerp3(u, cp[2], cp[3])];
+    let b = [lerp3(u, a[0], a[1]), lerp3(u, a[1], a[2])];
+    lerp3(u, b[0], b[1])
+}
+
+fn bezier_derivative(cp: &[Vec3; 4], u: f64) -> Vec3 {
+    let s = 1.0 - u;
+    3.0 * (s * s * (cp[1] - cp[0]) + 2.0 * s * u * (cp[2] - cp[1]) + u * u * (cp[3] - cp[2]))
+}
+
+// de casteljau split at the midpoint
+fn split_bezier(cp: &[Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let a = [0.5 * (cp[0] + cp[1]), 0.5 * (cp[1] + cp[2]), 0.5 * (cp[2] + cp[3])];
+    let b = [0.5 * (a[0] + a[1]), 0.5 * (a[1] + a[2])];
+    let mid = 0.5 * (b[0] + b[1]);
+    ([cp[0], a[0], b[0], mid], [mid, b[1], a[2], cp[3]])
+}
+
+fn lerp3(t: f64, a: Vec3, b: Vec3) -> Vec3 {
+    (1.0 - t) * a + t * b
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{Curve, CurveMode, CurveSet};
+    use crate::hit::{HitRecord, Hittable, Lambertian};
+    use crate::ray::Ray;
+    use crate::vec3::{Colour, Point3, Vec3};
+    use std::sync::Arc;
+
+    #[test]
+    fn hits_within_width() {
+        let material = Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5)));
+        // gently bent strand along x
+        let cp = [
+            Point3::new(-1.0, 0.0, 0.0),
+            Point3::new(-0.3, 0.2, 0.0),
+            Point3::new(0.3, 0.2, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+        ];
+        let flat = CurveSet::new(vec![Curve::new(cp, (0.1, 0.1), CurveMode::Flat)], material.clone());
+        let tube = CurveSet::new(vec![Curve::new(cp, (0.1, 0.1), CurveMode::Cylinder)], material);
+        let mut rec = HitRecord::new();
+
+        // through the middle of the strand, which peaks at y = 0.15
+        let ray = Ray::new(Point3::new(0.0, 0.15, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(flat.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 5.0).abs() < 1e-3);
+        assert!((rec.u() - 0.5).abs() < 1e-2);
+        assert!(tube.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 4.95).abs() < 1e-2);
+
+        // just outside the half width
+        let ray = Ray::new(Point3::new(0.0, 0.22, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!flat.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+
+        // tube normals tilt towards the edge that was hit
+        let ray = Ray::new(Point3::new(0.0, 0.19, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(tube.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!(rec.norm().y() > 0.5);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::constants::pi;
+use crate::ray::Ray;
+use crate::spectrum::{self, Wavelengths};
+use crate::vec3::{Colour, Vec3};
+
+// where the RGB channels are evaluated when something needs a wavelength, in nm
+pub const RGB_LAMBDA: [f64; 3] = [650.0, 550.0, 450.0];
+
+/* @brief Unpolarised fresnel reflectance at a smooth dielectric boundary,
+ *  eta is the index on the far side over the index on the incident side.
+ *  A negative cosine means the ray arrives from the far side.
+ */
+pub fn fr_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let (cos_i, eta) = if cos_theta_i < 0.0 {
+        (-cos_theta_i, 1.0 / eta)
+    } else {
+        (cos_theta_i, eta)
+    };
+    let cos_i = cos_i.min(1.0);
+
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        // total internal reflection
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let r_parl = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_parl * r_parl + r_perp * r_perp)
+}
+
+/* @brief Fresnel reflectance of a conductor with complex index eta + ik, one
+ *  channel at a time
+ */
+pub fn fr_conductor(cos_theta_i: f64, eta: Colour, k: Colour) -> Colour {
+    let mut out = Colour::new_z();
+    for i in 0..3 {
+        out[i] = fr_complex(cos_theta_i, Complex::new(eta[i], k[i]));
+    }
+    out
+}
+
+fn fr_complex(cos_theta_i: f64, eta: Complex) -> f64 {
+    let cos_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_i = 1.0 - cos_i * cos_i;
+    // snell's law with a complex index
+    let sin2_t = Complex::new(sin2_i, 0.0).div(eta.mul(eta));
+    let cos_t = Complex::new(1.0, 0.0).sub(sin2_t).sqrt();
+
+    let ci = Complex::new(cos_i, 0.0);
+    let r_parl = eta.mul(ci).sub(cos_t).div(eta.mul(ci).add(cos_t));
+    let r_perp = ci.sub(eta.mul(cos_t)).div(ci.add(eta.mul(cos_t)));
+    0.5 * (r_parl.norm() + r_perp.norm())
+}
+
+/* A thin transparent film over a surface, as on a soap bubble or an oil slick. Light
+ *  reflected off the top of the film interferes with light reflected off the surface
+ *  beneath, so the fresnel term swings with wavelength and angle.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct ThinFilm {
+    // in nm
+    thickness: f64,
+    ior: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f64, ior: f64) -> Self {
+        Self { thickness, ior }
+    }
+
+    /* @brief Reflectance at one wavelength (nm) of the film over a substrate of index
+     *  eta + ik, arriving from a medium of index n_incident
+     */
+    pub fn reflectance(&self, cos_theta_i: f64, n_incident: f64, eta: f64, k: f64, lambda: f64) -> f64 {
+        let cos1 = cos_theta_i.clamp(0.0, 1.0);
+        let sin2 = 1.0 - cos1 * cos1;
+        let n1 = Complex::new(1.0, 0.0);
+        let n2 = Complex::new(self.ior / n_incident, 0.0);
+        let n3 = Complex::new(eta / n_incident, k / n_incident);
+        // snell's law into each layer, complex past the critical angle or in a conductor
+        let cos_in = |n: Complex| Complex::new(1.0, 0.0).sub(Complex::new(sin2, 0.0).div(n.mul(n))).sqrt();
+        let (c1, c2, c3) = (Complex::new(cos1, 0.0), cos_in(n2), cos_in(n3));
+
+        // phase picked up on the way down through the film and back
+        let phase = Complex::new(4.0 * pi * self.thickness / lambda, 0.0).mul(n2).mul(c2);
+        let round_trip = Complex::new(-phase.im, phase.re).exp();
+        let airy = |r12: Complex, r23: Complex| {
+            let r23 = r23.mul(round_trip);
+            r12.add(r23).div(Complex::new(1.0, 0.0).add(r12.mul(r23))).norm()
+        };
+        let rs = |ni: Complex, ci: Complex, nj: Complex, cj: Complex| {
+            ni.mul(ci).sub(nj.mul(cj)).div(ni.mul(ci).add(nj.mul(cj)))
+        };
+        let rp = |ni: Complex, ci: Complex, nj: Complex, cj: Complex| {
+            nj.mul(ci).sub(ni.mul(cj)).div(nj.mul(ci).add(ni.mul(cj)))
+        };
+        0.5 * (airy(rs(n1, c1, n2, c2), rs(n2, c2, n3, c3)) + airy(rp(n1, c1, n2, c2), rp(n2, c2, n3, c3)))
+    }
+
+    /* @brief Reflectance as the ray sees it. RGB renders get the three channel
+     *  wavelengths; spectral ones get the hero wavelength alone, and the ray leaving the
+     *  surface has to carry on with the returned wavelengths.
+     */
+    pub fn reflectance_for(&self, ray: &Ray, cos_theta_i: f64, n_incident: f64, eta: Colour, k: Colour) -> (Colour, Option<Wavelengths>) {
+        match ray.wavelengths() {
+            Some(w) => {
+                let at = Vec3::new(w.hero(), w.hero(), w.hero());
+                let (eta, k) = (spectrum::upsample(eta, at).x(), spectrum::upsample(k, at).x());
+                let r = self.reflectance(cos_theta_i, n_incident, eta, k, w.hero());
+                (Colour::new(r, r, r), Some(w.terminate_secondary()))
+            }
+            None => {
+                let mut out = Colour::new_z();
+                for i in 0..3 {
+                    out[i] = self.reflectance(cos_theta_i, n_incident, eta[i], k[i], RGB_LAMBDA[i]);
+                }
+                (out, None)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn add(self, o: Self) -> Self {
+        Self::new(self.re + o.re, self.im + o.im)
+    }
+
+    fn sub(self, o: Self) -> Self {
+        Self::new(self.re - o.re, self.im - o.im)
+    }
+
+    fn mul(self, o: Self) -> Self {
+        Self::new(self.re * o.re - self.im * o.im, self.re * o.im + self.im * o.re)
+    }
+
+    fn div(self, o: Self) -> Self {
+        let scale = 1.0 / o.norm();
+        Self::new(
+            scale * (self.re * o.re + self.im * o.im),
+            scale * (self.im * o.re - self.re * o.im),
+        )
+    }
+
+    fn exp(self) -> Self {
+        let m = self.re.exp();
+        Self::new(m * self.im.cos(), m * self.im.sin())
+    }
+
+    // squared magnitude
+    fn norm(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // principal square root
+    fn sqrt(self) -> Self {
+        let n = self.norm().sqrt();
+        if n == 0.0 {
+            return Self::new(0.0, 0.0);
+        }
+        let t1 = (0.5 * (n + self.re.abs())).sqrt();
+        let t2 = 0.5 * self.im / t1;
+        if self.re >= 0.0 {
+            Self::new(t1, t2)
+        } else {
+            Self::new(t2.abs(), t1.copysign(self.im))
+        }
+    }
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{fr_conductor, fr_dielectric, ThinFilm};
+    use crate::vec3::Colour;
+
+    #[test]
+    fn normal_and_grazing_incidence() {
+        assert!((fr_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+        assert_eq!(fr_dielectric(-0.1, 1.5), 1.0);
+        // with no absorption the conductor formula is the dielectric one
+        let f = fr_conductor(0.6, Colour::new(1.5, 1.5, 1.5), Colour::new_z());
+        assert!((f.x() - fr_dielectric(0.6, 1.5)).abs() < 1e-12);
+        // head on: ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2)
+        let f = fr_conductor(1.0, Colour::new(0.143, 0.143, 0.143), Colour::new(3.983, 3.983, 3.983));
+        assert!((f.y() - 0.9667).abs() < 1e-3);
+        assert!((fr_conductor(0.0, Colour::new(0.2, 0.9, 1.1), Colour::new(3.9, 2.4, 2.1)).z() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn thin_film_interference() {
+        // a film of no thickness is just the bare surface
+        let bare = ThinFilm::new(0.0, 1.33);
+        for cos in [1.0, 0.7, 0.2] {
+            assert!((bare.reflectance(cos, 1.0, 1.5, 0.0, 550.0) - fr_dielectric(cos, 1.5)).abs() < 1e-12);
+        }
+        // a quarter wave coating of index sqrt(n) cancels the reflection at normal incidence
+        let n = 1.5_f64.sqrt();
+        let coating = ThinFilm::new(550.0 / (4.0 * n), n);
+        assert!(coating.reflectance(1.0, 1.0, 1.5, 0.0, 550.0) < 1e-12);
+        assert!(coating.reflectance(1.0, 1.0, 1.5, 0.0, 450.0) > 1e-4);
+    }
+}
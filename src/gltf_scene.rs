@@ -0,0 +1,628 @@
+use crate::camera::Camera;
+use crate::hit::{Dielectric, HittableList, Lambertian, Material, Metal};
+use crate::mesh::{MeshData, TriangleMesh};
+use crate::texture::{ImageTexture, Product, SolidColour, Texture, VertexColours};
+use crate::vec3::{Colour, Point3, Vec3};
+use gltf::buffer::Source;
+use gltf::camera::Projection;
+use gltf::image;
+use gltf::mesh::Mode;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path};
+use std::sync::Arc;
+
+type Mat4 = [[f64; 4]; 4];
+
+const IDENTITY: Mat4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/* What a glTF camera node amounts to in `Camera::new` terms. glTF cameras are
+ *  pinholes so there is no aperture, and the aspect ratio is optional.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct CameraParams {
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    // in degrees
+    pub vfov: f64,
+    pub aspect_ratio: Option<f64>,
+    // width and height of the view for orthographic cameras, which ignore vfov
+    pub ortho: Option<(f64, f64)>,
+}
+
+impl CameraParams {
+    // aspect_ratio is used when the file does not pin one down
+    pub fn camera(&self, aspect_ratio: f64) -> Camera {
+        if let Some((width, height)) = self.ortho {
+            return Camera::orthographic(self.lookfrom, self.lookat, self.vup, width, height);
+        }
+        let focus_dist = (self.lookat - self.lookfrom).length();
+        Camera::new(
+            self.lookfrom,
+            self.lookat,
+            self.vup,
+            self.vfov,
+            self.aspect_ratio.unwrap_or(aspect_ratio),
+            0.0,
+            focus_dist,
+        )
+    }
+}
+
+pub struct GltfScene {
+    pub world: HittableList,
+    pub cameras: Vec<CameraParams>,
+    // how the primitives that were left out are drawn, only triangles are loaded
+    pub skipped: Vec<Mode>,
+}
+
+/* @brief Loads the default scene of a .gltf or .glb file. Buffers and images may be
+ *  embedded, in the glb blob or files in the asset's directory; nothing is fetched
+ *  over the network or from elsewhere on disk.
+ */
+pub fn load(path: impl AsRef<Path>) -> io::Result<GltfScene> {
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    parse(&bytes, base)
+}
+
+pub fn parse(bytes: &[u8], base: &Path) -> io::Result<GltfScene> {
+    let gltf = gltf::Gltf::from_slice(bytes).map_err(|e| invalid(&e.to_string()))?;
+
+    let mut buffers = Vec::new();
+    for buffer in gltf.buffers() {
+        let data = match buffer.source() {
+            Source::Bin => gltf.blob.clone().ok_or_else(|| invalid("glb blob missing"))?,
+            Source::Uri(uri) => read_uri(uri, base)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(invalid("buffer shorter than declared"));
+        }
+        buffers.push(data);
+    }
+
+    let scene = gltf
+        .default_scene()
+        .or_else(|| gltf.scenes().next())
+        .ok_or_else(|| invalid("no scene"))?;
+
+    let mut loader = Loader {
+        buffers: &buffers,
+        base,
+        materials: HashMap::new(),
+        images: HashMap::new(),
+        world: HittableList::new(),
+        cameras: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for node in scene.nodes() {
+        loader.visit(&node, &IDENTITY)?;
+    }
+    Ok(GltfScene { world: loader.world, cameras: loader.cameras, skipped: loader.skipped })
+}
+
+struct Loader<'a> {
+    buffers: &'a [Vec<u8>],
+    base: &'a Path,
+    // keyed by material index, None being the glTF default material
+    materials: HashMap<Option<usize>, Arc<dyn Material + Sync + Send>>,
+    // decoded base colour images, keyed by image index
+    images: HashMap<usize, Arc<ImageTexture>>,
+    world: HittableList,
+    cameras: Vec<CameraParams>,
+    skipped: Vec<Mode>,
+}
+
+impl<'a> Loader<'a> {
+    fn visit(&mut self, node: &gltf::Node, parent: &Mat4) -> io::Result<()> {
+        let local = node.transform().matrix().map(|c| c.map(|x| x as f64));
+        let world = mat_mul(parent, &local);
+
+        if let Some(camera) = node.camera() {
+            // cameras look down their local -z with +y up
+            let lookfrom = transform_point(&world, Vec3::new_z());
+            let forward = Vec3::unit_vector(transform_vector(&world, Vec3::new(0.0, 0.0, -1.0)));
+            let mut params = CameraParams {
+                lookfrom,
+                lookat: lookfrom + forward,
+                vup: Vec3::unit_vector(transform_vector(&world, Vec3::new(0.0, 1.0, 0.0))),
+                vfov: 0.0,
+                aspect_ratio: None,
+                ortho: None,
+            };
+            match camera.projection() {
+                Projection::Perspective(p) => {
+                    params.vfov = (p.yfov() as f64).to_degrees();
+                    params.aspect_ratio = p.aspect_ratio().map(|a| a as f64);
+                }
+                Projection::Orthographic(o) => {
+                    // magnifications are half the view
+                    let (width, height) = (2.0 * o.xmag() as f64, 2.0 * o.ymag() as f64);
+                    params.aspect_ratio = Some(width / height);
+                    params.ortho = Some((width, height));
+                }
+            }
+            self.cameras.push(params);
+        }
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.add_primitive(&primitive, &world)?;
+            }
+        }
+
+        for child in node.children() {
+            self.visit(&child, &world)?;
+        }
+        Ok(())
+    }
+
+    fn add_primitive(&mut self, primitive: &gltf::Primitive, world: &Mat4) -> io::Result<()> {
+        if primitive.mode() != Mode::Triangles {
+            self.skipped.push(primitive.mode());
+            return Ok(());
+        }
+        let buffers = self.buffers;
+        let reader = primitive.reader(|b| buffers.get(b.index()).map(|d| &d[..]));
+        let to_vec = |p: [f32; 3]| Vec3::new(p[0] as f64, p[1] as f64, p[2] as f64);
+
+        let positions: Vec<Point3> = reader
+            .read_positions()
+            .ok_or_else(|| invalid("primitive without positions"))?
+            .map(|p| transform_point(world, to_vec(p)))
+            .collect();
+        let normal_matrix = normal_matrix(world);
+        let normals = reader
+            .read_normals()
+            .map(|n| n.map(|v| Vec3::unit_vector(transform_vector(&normal_matrix, to_vec(v)))).collect());
+        // the set the base colour texture reads, glTF puts the uv origin top left where
+        //  the textures here have v going up
+        let set = primitive.material().pbr_metallic_roughness().base_color_texture().map_or(0, |t| t.tex_coord());
+        let uvs = reader
+            .read_tex_coords(set)
+            .map(|t| t.into_f32().map(|[u, v]| (u as f64, 1.0 - v as f64)).collect());
+        let colours = reader
+            .read_colors(0)
+            .map(|c| c.into_rgb_f32().map(|[r, g, b]| Colour::new(r as f64, g as f64, b as f64)).collect());
+        let flat: Vec<usize> = match reader.read_indices() {
+            Some(i) => i.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+        let mut indices: Vec<[usize; 3]> = flat.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+        // a mirroring transform flips the winding
+        if determinant(world) < 0.0 {
+            indices.iter_mut().for_each(|f| f.swap(1, 2));
+        }
+
+        let mut data = MeshData { positions, normals, uvs, colours, indices };
+        data.check_indices();
+
+        let data = Arc::new(data);
+        let material = match VertexColours::new(data.clone()) {
+            // vertex colours scale the base colour of a diffuse material
+            Some(colours) if !is_metal_or_glass(&primitive.material()) => {
+                let base = self.base_colour(&primitive.material())?;
+                Arc::new(Lambertian::textured(Arc::new(Product::new(base, Arc::new(colours)))))
+            }
+            _ => self.material(&primitive.material())?,
+        };
+        self.world.add(Arc::new(TriangleMesh::from_shared(data, material)));
+        Ok(())
+    }
+
+    /* @brief Closest match among the renderer's materials: transmissive becomes glass,
+     *  metallic becomes metal with roughness as fuzz, everything else diffuse. Only the
+     *  diffuse one picks up the base colour texture, metal keeps to the factor.
+     */
+    fn material(&mut self, material: &gltf::Material) -> io::Result<Arc<dyn Material + Sync + Send>> {
+        if let Some(cached) = self.materials.get(&material.index()) {
+            return Ok(cached.clone());
+        }
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let transmission = material.transmission().map_or(0.0, |t| t.transmission_factor());
+        let converted: Arc<dyn Material + Sync + Send> = if transmission > 0.5 {
+            Arc::new(Dielectric::new(material.ior().unwrap_or(1.5) as f64))
+        } else if pbr.metallic_factor() > 0.5 {
+            Arc::new(Metal::new(Colour::new(r as f64, g as f64, b as f64), pbr.roughness_factor() as f64))
+        } else {
+            Arc::new(Lambertian::textured(self.base_colour(material)?))
+        };
+        self.materials.insert(material.index(), converted.clone());
+        Ok(converted)
+    }
+
+    // the base colour factor, times the base colour texture when there is one
+    fn base_colour(&mut self, material: &gltf::Material) -> io::Result<Arc<dyn Texture + Sync + Send>> {
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let factor = Arc::new(SolidColour::new(Colour::new(r as f64, g as f64, b as f64)));
+        match pbr.base_color_texture() {
+            Some(info) => Ok(Arc::new(Product::new(factor, self.image(&info.texture().source())?))),
+            None => Ok(factor),
+        }
+    }
+
+    fn image(&mut self, image: &gltf::Image) -> io::Result<Arc<ImageTexture>> {
+        if let Some(cached) = self.images.get(&image.index()) {
+            return Ok(cached.clone());
+        }
+        let texture = match image.source() {
+            image::Source::View { view, .. } => {
+                let bytes = self
+                    .buffers
+                    .get(view.buffer().index())
+                    .and_then(|b| b.get(view.offset()..view.offset() + view.length()))
+                    .ok_or_else(|| invalid("image view past the end of its buffer"))?;
+                ImageTexture::decode(bytes)?
+            }
+            image::Source::Uri { uri, .. } => ImageTexture::decode(&read_uri(uri, self.base)?)?,
+        };
+        let texture = Arc::new(texture);
+        self.images.insert(image.index(), texture.clone());
+        Ok(texture)
+    }
+}
+
+fn is_metal_or_glass(material: &gltf::Material) -> bool {
+    material.pbr_metallic_roughness().metallic_factor() > 0.5
+        || material.transmission().map_or(0.0, |t| t.transmission_factor()) > 0.5
+}
+
+/* @brief Base64 data uris, or relative paths that stay inside the asset's directory.
+ *  Any other scheme, file: included, absolute paths and .. are refused.
+ */
+fn read_uri(uri: &str, base: &Path) -> io::Result<Vec<u8>> {
+    if let Some(rest) = uri.strip_prefix("data:") {
+        let (_, payload) = rest
+            .split_once(";base64,")
+            .ok_or_else(|| invalid("only base64 data uris are supported"))?;
+        return decode_base64(payload);
+    }
+    if has_scheme(uri) {
+        return Err(invalid(&format!("refusing to load non local uri {uri}")));
+    }
+    // checked after decoding so %2e%2e can't sneak a parent past
+    let decoded = percent_decode(uri)?;
+    let path = Path::new(&decoded);
+    if !path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(invalid(&format!("refusing to load {uri} from outside the asset's directory")));
+    }
+    fs::read(base.join(path))
+}
+
+// scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." ) ":", which also covers drive letters
+fn has_scheme(uri: &str) -> bool {
+    match uri.split_once(':') {
+        Some((scheme, _)) => {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        }
+        None => false,
+    }
+}
+
+fn percent_decode(text: &str) -> io::Result<String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+                .ok_or_else(|| invalid("bad percent escape"))?;
+            out.push(u8::from_str_radix(std::str::from_utf8(hex).unwrap(), 16).unwrap());
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|_| invalid("uri is not utf-8"))
+}
+
+fn decode_base64(text: &str) -> io::Result<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        acc = acc << 6 | value(c).ok_or_else(|| invalid("bad base64"))? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+// matrices are column major, m[column][row], as glTF stores them
+fn mat_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut m = [[0.0; 4]; 4];
+    for (c, col) in m.iter_mut().enumerate() {
+        for (r, val) in col.iter_mut().enumerate() {
+            *val = (0..4).map(|k| a[k][r] * b[c][k]).sum();
+        }
+    }
+    m
+}
+
+fn transform_point(m: &Mat4, p: Point3) -> Point3 {
+    transform_vector(m, p) + Vec3::new(m[3][0], m[3][1], m[3][2])
+}
+
+fn transform_vector(m: &Mat4, v: Vec3) -> Vec3 {
+    Vec3::new(
+        m[0][0] * v.x() + m[1][0] * v.y() + m[2][0] * v.z(),
+        m[0][1] * v.x() + m[1][1] * v.y() + m[2][1] * v.z(),
+        m[0][2] * v.x() + m[1][2] * v.y() + m[2][2] * v.z(),
+    )
+}
+
+fn determinant(m: &Mat4) -> f64 {
+    let (a, b, c) = (
+        Vec3::new(m[0][0], m[0][1], m[0][2]),
+        Vec3::new(m[1][0], m[1][1], m[1][2]),
+        Vec3::new(m[2][0], m[2][1], m[2][2]),
+    );
+    Vec3::dot(a, Vec3::cross(b, c))
+}
+
+/* @brief Inverse transpose of the upper 3x3, which carries normals. The scale doesn't
+ *  matter but the sign does, a mirroring transform would otherwise turn them inward.
+ */
+fn normal_matrix(m: &Mat4) -> Mat4 {
+    let (a, b, c) = (
+        Vec3::new(m[0][0], m[0][1], m[0][2]),
+        Vec3::new(m[1][0], m[1][1], m[1][2]),
+        Vec3::new(m[2][0], m[2][1], m[2][2]),
+    );
+    // rows of the inverse are the cross products of the columns
+    let (r0, r1, r2) = (Vec3::cross(b, c), Vec3::cross(c, a), Vec3::cross(a, b));
+    let sign = Vec3::dot(a, r0).signum();
+    let (r0, r1, r2) = (sign * r0, sign * r1, sign * r2);
+    let mut n = IDENTITY;
+    for (i, r) in [r0, r1, r2].iter().enumerate() {
+        n[i] = [r.x(), r.y(), r.z(), 0.0];
+    }
+    n
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("gltf: {msg}"))
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{parse, read_uri};
+    use gltf::mesh::Mode;
+    use crate::hit::{HitRecord, Hittable};
+    use crate::ray::Ray;
+    use crate::vec3::{Colour, Point3, Vec3};
+    use std::fs;
+    use std::path::Path;
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::new();
+        for chunk in bytes.chunks(3) {
+            let n = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+            for i in 0..=chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 63) as usize] as char);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn nodes_cameras_and_materials() {
+        let mut bin = Vec::new();
+        for c in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bin.extend_from_slice(&c.to_le_bytes());
+        }
+        let json = format!(
+            r#"{{
+  "asset": {{ "version": "2.0" }},
+  "scene": 0,
+  "scenes": [ {{ "nodes": [0, 2, 3] }} ],
+  "nodes": [
+    {{ "translation": [0, 0, -5], "children": [1] }},
+    {{ "mesh": 0, "scale": [2, 2, 2] }},
+    {{ "camera": 0, "translation": [0, 1, 3] }},
+    {{ "camera": 1, "translation": [0.5, 0.5, 3] }}
+  ],
+  "cameras": [
+    {{ "type": "perspective", "perspective": {{ "yfov": 0.5, "znear": 0.1 }} }},
+    {{ "type": "orthographic", "orthographic": {{ "xmag": 2, "ymag": 1, "znear": 0.1, "zfar": 100 }} }}
+  ],
+  "materials": [ {{ "pbrMetallicRoughness": {{ "baseColorFactor": [1, 0.8, 0.2, 1], "metallicFactor": 1.0, "roughnessFactor": 0.2 }} }} ],
+  "meshes": [ {{ "primitives": [ {{ "attributes": {{ "POSITION": 0 }}, "material": 0 }} ] }} ],
+  "buffers": [ {{ "byteLength": 36, "uri": "data:application/octet-stream;base64,{}" }} ],
+  "bufferViews": [ {{ "buffer": 0, "byteLength": 36 }} ],
+  "accessors": [ {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }} ]
+}}"#,
+            encode_base64(&bin)
+        );
+        let scene = parse(json.as_bytes(), Path::new(".")).unwrap();
+
+        assert_eq!(scene.cameras.len(), 2);
+        let cam = scene.cameras[0];
+        assert!((cam.lookfrom - Point3::new(0.0, 1.0, 3.0)).near_zero());
+        assert!((cam.vfov - 0.5f64.to_degrees()).abs() < 1e-6);
+        assert!(cam.aspect_ratio.is_none());
+
+        // orthographic rays all run straight down -z from the 4 by 2 view
+        let ortho = scene.cameras[1].camera(1.0);
+        assert_eq!(ortho.aspect_ratio, 2.0);
+        let (a, b) = (ortho.get_ray(0.0, 0.0).unwrap(), ortho.get_ray(1.0, 1.0).unwrap());
+        assert!((a.origin() - Point3::new(-1.5, -0.5, 3.0)).near_zero());
+        assert!((b.origin() - Point3::new(2.5, 1.5, 3.0)).near_zero());
+        assert!((a.direction() - b.direction()).near_zero());
+        assert!((a.direction() - Vec3::new(0.0, 0.0, -1.0)).near_zero());
+
+        // the triangle was scaled by 2 and pushed back to z = -5
+        let mut rec = HitRecord::new();
+        let ray = Ray::new(Point3::new(0.5, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(scene.world.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.point().z() + 5.0).abs() < 1e-6);
+        // the metallic material comes through as metal, base colour as the albedo and
+        //  roughness as the fuzz around the mirror direction
+        let material = rec.material.clone().unwrap();
+        let mut widest: f64 = 0.0;
+        for _ in 0..200 {
+            let (mut attenuation, mut scattered) = (Colour::new(0.0, 0.0, 0.0), Ray::default());
+            let ray = Ray::new(Point3::new(0.5, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+            material.scatter(ray, &rec, &mut attenuation, &mut scattered);
+            assert!((attenuation - Colour::new(1.0, 0.8, 0.2)).length() < 1e-6);
+            widest = widest.max((scattered.direction() - Vec3::new(0.0, 0.0, 1.0)).length());
+        }
+        assert!(widest > 0.1 && widest <= 0.2 + 1e-9, "{widest}");
+        let ray = Ray::new(Point3::new(1.2, 0.6, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(scene.world.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        let ray = Ray::new(Point3::new(2.5, 0.1, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!scene.world.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn uris_stay_inside_the_asset_directory() {
+        let base = std::env::temp_dir().join(format!("ray_tracer_gltf_{}", std::process::id()));
+        fs::create_dir_all(base.join("sub dir")).unwrap();
+        fs::write(base.join("sub dir/a+b.bin"), b"inside").unwrap();
+
+        assert_eq!(read_uri("sub%20dir/a%2Bb.bin", &base).unwrap(), b"inside");
+        assert_eq!(read_uri("./sub%20dir/a+b.bin", &base).unwrap(), b"inside");
+        assert_eq!(read_uri("data:application/octet-stream;base64,aW5zaWRl", &base).unwrap(), b"inside");
+        for uri in [
+            "file:///etc/passwd",
+            "file:etc/passwd",
+            "https://example.com/a.bin",
+            "C:/Windows/win.ini",
+            "/etc/passwd",
+            "../a.bin",
+            "sub%20dir/../../a.bin",
+            "%2e%2e/a.bin",
+            "%2Fetc%2Fpasswd",
+            "sub%2",
+            "sub%zzdir",
+        ] {
+            assert!(read_uri(uri, &base).is_err(), "{uri} was read");
+        }
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn base_colour_texture_and_vertex_colours_multiply() {
+        // 1 by 2 png, red on top of green
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 1, 2);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[255, 0, 0, 0, 255, 0]).unwrap();
+        writer.finish().unwrap();
+
+        let mut bin = Vec::new();
+        for c in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bin.extend_from_slice(&c.to_le_bytes());
+        }
+        // every corner on the centre of the top texel, glTF v runs down
+        for c in [0.5f32, 0.25, 0.5, 0.25, 0.5, 0.25] {
+            bin.extend_from_slice(&c.to_le_bytes());
+        }
+        for c in [0.5f32; 9] {
+            bin.extend_from_slice(&c.to_le_bytes());
+        }
+        let image_offset = bin.len();
+        bin.extend_from_slice(&png);
+
+        let json = format!(
+            r#"{{
+  "asset": {{ "version": "2.0" }},
+  "scenes": [ {{ "nodes": [0] }} ],
+  "nodes": [ {{ "mesh": 0 }} ],
+  "materials": [ {{ "pbrMetallicRoughness": {{ "baseColorFactor": [0.5, 1, 1, 1], "baseColorTexture": {{ "index": 0 }}, "metallicFactor": 0.0 }} }} ],
+  "textures": [ {{ "source": 0 }} ],
+  "images": [ {{ "bufferView": 3, "mimeType": "image/png" }} ],
+  "meshes": [ {{ "primitives": [ {{ "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1, "COLOR_0": 2 }}, "material": 0 }} ] }} ],
+  "buffers": [ {{ "byteLength": {}, "uri": "data:application/octet-stream;base64,{}" }} ],
+  "bufferViews": [
+    {{ "buffer": 0, "byteLength": 36 }},
+    {{ "buffer": 0, "byteOffset": 36, "byteLength": 24 }},
+    {{ "buffer": 0, "byteOffset": 60, "byteLength": 36 }},
+    {{ "buffer": 0, "byteOffset": {}, "byteLength": {} }}
+  ],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }},
+    {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" }},
+    {{ "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC3" }}
+  ]
+}}"#,
+            bin.len(),
+            encode_base64(&bin),
+            image_offset,
+            png.len()
+        );
+        let scene = parse(json.as_bytes(), Path::new(".")).unwrap();
+
+        let mut rec = HitRecord::new();
+        let ray = Ray::new(Point3::new(0.2, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(scene.world.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        let (mut attenuation, mut scattered) = (Colour::new(0.0, 0.0, 0.0), Ray::default());
+        rec.material.clone().unwrap().scatter(ray, &rec, &mut attenuation, &mut scattered);
+        // factor 0.5 times the red texel times the grey vertex colour
+        assert!((attenuation - Colour::new(0.25, 0.0, 0.0)).near_zero(), "{attenuation:?}");
+    }
+
+    #[test]
+    fn mirrored_nodes_keep_normals_outward() {
+        let mut bin = Vec::new();
+        for c in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bin.extend_from_slice(&c.to_le_bytes());
+        }
+        for c in [0.0f32, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0] {
+            bin.extend_from_slice(&c.to_le_bytes());
+        }
+        let json = format!(
+            r#"{{
+  "asset": {{ "version": "2.0" }},
+  "scenes": [ {{ "nodes": [0] }} ],
+  "nodes": [ {{ "mesh": 0, "scale": [-1, 1, 1] }} ],
+  "meshes": [ {{ "primitives": [ {{ "attributes": {{ "POSITION": 0, "NORMAL": 1 }} }}, {{ "attributes": {{ "POSITION": 0 }}, "mode": 1 }} ] }} ],
+  "buffers": [ {{ "byteLength": 72, "uri": "data:application/octet-stream;base64,{}" }} ],
+  "bufferViews": [ {{ "buffer": 0, "byteLength": 36 }}, {{ "buffer": 0, "byteOffset": 36, "byteLength": 36 }} ],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }},
+    {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }}
+  ]
+}}"#,
+            encode_base64(&bin)
+        );
+        let scene = parse(json.as_bytes(), Path::new(".")).unwrap();
+        assert_eq!(scene.skipped, vec![Mode::Lines]);
+
+        // mirrored in x the triangle still faces +z, as do its normals
+        let mut rec = HitRecord::new();
+        let ray = Ray::new(Point3::new(-0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(scene.world.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!(rec.front_face);
+        assert!((rec.norm() - Vec3::new(0.0, 0.0, 1.0)).near_zero());
+    }
+}
@@ -0,0 +1,301 @@
+use crate::constants::pi;
+use crate::fresnel::fr_dielectric;
+use crate::hit::{HitRecord, Material};
+use crate::ray::Ray;
+use crate::vec3::{Colour, Vec3};
+use rand::Rng;
+
+// scattering lobes tracked explicitly: R, TT, TRT. Everything after is lumped into one
+const P_MAX: usize = 3;
+
+/* Fiber scattering after d'Eon et al. and Chiang et al. (the model pbrt uses), meant
+ *  for strands from `CurveSet`. Light either reflects off the cuticle (R), passes
+ *  through the fiber (TT), or bounces once inside it (TRT), each lobe shifted by the
+ *  tilt of the cuticle scales and absorbed according to the path length inside.
+ */
+pub struct Hair {
+    // absorption coefficient inside the fiber, per unit of fiber radius
+    sigma_a: Colour,
+    eta: f64,
+    // longitudinal variances and azimuthal logistic scale derived from the roughnesses
+    v: [f64; P_MAX + 1],
+    s: f64,
+    // sin/cos of 2^k times the scale tilt
+    sin_2k_alpha: [f64; 3],
+    cos_2k_alpha: [f64; 3],
+}
+
+impl Hair {
+    /* @brief beta_m and beta_n are the longitudinal and azimuthal roughness in 0..1,
+     *  alpha is the cuticle scale tilt in degrees (around 2 for human hair)
+     */
+    pub fn new(sigma_a: Colour, eta: f64, beta_m: f64, beta_n: f64, alpha: f64) -> Self {
+        let v0 = (0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20)).powi(2);
+        let v = [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0];
+        let s = (pi / 8.0).sqrt() * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22));
+
+        let mut sin_2k_alpha = [alpha.to_radians().sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [safe_sqrt(1.0 - sin_2k_alpha[0].powi(2)), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+        Self { sigma_a, eta, v, s, sin_2k_alpha, cos_2k_alpha }
+    }
+
+    /* @brief Colour from pigment concentrations, roughly 0..8 eumelanin for blond
+     *  through black and a little pheomelanin for red
+     */
+    pub fn from_melanin(eumelanin: f64, pheomelanin: f64, beta_m: f64, beta_n: f64) -> Self {
+        let sigma_a = eumelanin * Colour::new(0.419, 0.697, 1.37) + pheomelanin * Colour::new(0.187, 0.4, 1.05);
+        Self::new(sigma_a, 1.55, beta_m, beta_n, 2.0)
+    }
+
+    // longitudinal angle of the outgoing direction tilted by the scales for lobe p
+    fn tilt(&self, p: usize, sin_theta_o: f64, cos_theta_o: f64) -> (f64, f64) {
+        let (s2k, c2k) = (&self.sin_2k_alpha, &self.cos_2k_alpha);
+        match p {
+            0 => (sin_theta_o * c2k[1] - cos_theta_o * s2k[1], cos_theta_o * c2k[1] + sin_theta_o * s2k[1]),
+            1 => (sin_theta_o * c2k[0] + cos_theta_o * s2k[0], cos_theta_o * c2k[0] - sin_theta_o * s2k[0]),
+            2 => (sin_theta_o * c2k[2] + cos_theta_o * s2k[2], cos_theta_o * c2k[2] - sin_theta_o * s2k[2]),
+            _ => (sin_theta_o, cos_theta_o),
+        }
+    }
+
+    // attenuation of each lobe, transmittance through the fiber included
+    fn ap(&self, cos_theta_o: f64, h: f64, transmittance: Colour) -> [Colour; P_MAX + 1] {
+        let cos_gamma_o = safe_sqrt(1.0 - h * h);
+        let f = fr_dielectric(cos_theta_o * cos_gamma_o, self.eta);
+        let mut ap = [Colour::new_z(); P_MAX + 1];
+        ap[0] = Colour::new(f, f, f);
+        ap[1] = (1.0 - f) * (1.0 - f) * transmittance;
+        for p in 2..P_MAX {
+            ap[p] = ap[p - 1] * transmittance * f;
+        }
+        let tf = transmittance * f;
+        ap[P_MAX] = ap[P_MAX - 1] * tf * Colour::new(1.0 / (1.0 - tf.x()), 1.0 / (1.0 - tf.y()), 1.0 / (1.0 - tf.z()));
+        ap
+    }
+
+    fn transmittance(&self, sin_theta_o: f64, cos_theta_o: f64, h: f64) -> Colour {
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let sin_gamma_t = h / etap;
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t * sin_gamma_t);
+        let path = 2.0 * cos_gamma_t / cos_theta_t;
+        Colour::new(
+            (-self.sigma_a.x() * path).exp(),
+            (-self.sigma_a.y() * path).exp(),
+            (-self.sigma_a.z() * path).exp(),
+        )
+    }
+
+    /* @brief Evaluates f * |cos theta_i| and the pdf of sampling wi, both directions in
+     *  the fiber frame (x along the strand, z the surface normal)
+     */
+    fn eval(&self, wo: Vec3, wi: Vec3, h: f64) -> (Colour, f64) {
+        let sin_theta_o = wo.x();
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z().atan2(wo.y());
+        let sin_theta_i = wi.x();
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+        let phi_i = wi.z().atan2(wi.y());
+        let phi = phi_i - phi_o;
+
+        let gamma_o = h.clamp(-1.0, 1.0).asin();
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let gamma_t = (h / etap).clamp(-1.0, 1.0).asin();
+
+        let ap = self.ap(cos_theta_o, h, self.transmittance(sin_theta_o, cos_theta_o, h));
+        let ap_pdf = ap_pdf(&ap);
+
+        let mut f = Colour::new_z();
+        let mut pdf = 0.0;
+        for p in 0..P_MAX {
+            let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+            let mp = mp(cos_theta_i, cos_theta_op.abs(), sin_theta_i, sin_theta_op, self.v[p]);
+            let np = np(phi, p, self.s, gamma_o, gamma_t);
+            f += mp * np * ap[p];
+            pdf += mp * np * ap_pdf[p];
+        }
+        let mp = mp(cos_theta_i, cos_theta_o, sin_theta_i, sin_theta_o, self.v[P_MAX]);
+        f += mp * ap[P_MAX] / (2.0 * pi);
+        pdf += mp * ap_pdf[P_MAX] / (2.0 * pi);
+        (f, pdf)
+    }
+}
+
+impl Material for Hair {
+    fn scatter(&self, ray: Ray, record: &HitRecord, attenuation: &mut Colour, scattered: &mut Ray) -> bool {
+        // fiber frame: x along the strand, z towards the viewer, y across the width
+        let z = record.norm();
+        let x = Vec3::unit_vector(record.dpdu() - Vec3::dot(record.dpdu(), z) * z);
+        let y = Vec3::cross(z, x);
+        let to_local = |w: Vec3| Vec3::new(Vec3::dot(w, x), Vec3::dot(w, y), Vec3::dot(w, z));
+        let wo = to_local(-Vec3::unit_vector(ray.direction()));
+        let h = 2.0 * record.v() - 1.0;
+
+        let mut rng = rand::thread_rng();
+        let sin_theta_o = wo.x();
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z().atan2(wo.y());
+        let gamma_o = h.clamp(-1.0, 1.0).asin();
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let gamma_t = (h / etap).clamp(-1.0, 1.0).asin();
+
+        // pick a lobe in proportion to how much light it carries
+        let ap = self.ap(cos_theta_o, h, self.transmittance(sin_theta_o, cos_theta_o, h));
+        let ap_pdf = ap_pdf(&ap);
+        let pick: f64 = rng.gen_range(0.0..1.0);
+        let mut p = 0;
+        let mut acc = ap_pdf[0];
+        while p < P_MAX && pick >= acc {
+            p += 1;
+            acc += ap_pdf[p];
+        }
+
+        // longitudinal angle around the tilted specular cone
+        let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+        let u1: f64 = rng.gen_range(0.0..1.0f64).max(1e-5);
+        let cos_theta = 1.0 + self.v[p] * (u1 + (1.0 - u1) * (-2.0 / self.v[p]).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let cos_phi = (2.0 * pi * rng.gen_range(0.0..1.0)).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+
+        // azimuth from the logistic around the lobe's exit angle
+        let u2: f64 = rng.gen_range(0.0..1.0);
+        let dphi = if p < P_MAX {
+            phi(p, gamma_o, gamma_t) + sample_trimmed_logistic(u2, self.s, -pi, pi)
+        } else {
+            2.0 * pi * u2
+        };
+        let phi_i = phi_o + dphi;
+        let wi = Vec3::new(sin_theta_i, cos_theta_i * phi_i.cos(), cos_theta_i * phi_i.sin());
+
+        let (f, pdf) = self.eval(wo, wi, h);
+        if pdf <= 0.0 {
+            return false;
+        }
+        *attenuation = f / pdf;
+        *scattered = Ray::new(record.point(), wi.x() * x + wi.y() * y + wi.z() * z);
+        true
+    }
+}
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0.0).sqrt()
+}
+
+fn ap_pdf(ap: &[Colour; P_MAX + 1]) -> [f64; P_MAX + 1] {
+    let weights = ap.map(|a| (a.x() + a.y() + a.z()) / 3.0);
+    let sum: f64 = weights.iter().sum();
+    weights.map(|w| if sum > 0.0 { w / sum } else { 1.0 / (P_MAX + 1) as f64 })
+}
+
+// modified bessel function of the first kind, order 0
+fn i0(x: f64) -> f64 {
+    let mut val = 0.0;
+    let mut x2i = 1.0;
+    let mut ifact = 1.0;
+    let mut i4 = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            ifact *= i as f64;
+        }
+        val += x2i / (i4 * ifact * ifact);
+        x2i *= x * x;
+        i4 *= 4.0;
+    }
+    val
+}
+
+fn log_i0(x: f64) -> f64 {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * pi).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+// longitudinal scattering
+fn mp(cos_theta_i: f64, cos_theta_o: f64, sin_theta_i: f64, sin_theta_o: f64, v: f64) -> f64 {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        (log_i0(a) - b - 1.0 / v + std::f64::consts::LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        (-b).exp() * i0(a) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+// azimuthal exit angle of lobe p
+fn phi(p: usize, gamma_o: f64, gamma_t: f64) -> f64 {
+    2.0 * p as f64 * gamma_t - 2.0 * gamma_o + p as f64 * pi
+}
+
+fn logistic(x: f64, s: f64) -> f64 {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+fn trimmed_logistic(x: f64, s: f64, a: f64, b: f64) -> f64 {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64, a: f64, b: f64) -> f64 {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(a, s)) - 1.0).ln();
+    x.clamp(a, b)
+}
+
+// azimuthal scattering
+fn np(phi_rel: f64, p: usize, s: f64, gamma_o: f64, gamma_t: f64) -> f64 {
+    let mut dphi = phi_rel - phi(p, gamma_o, gamma_t);
+    while dphi > pi {
+        dphi -= 2.0 * pi;
+    }
+    while dphi < -pi {
+        dphi += 2.0 * pi;
+    }
+    trimmed_logistic(dphi, s, -pi, pi)
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::Hair;
+    use crate::hit::{HitRecord, Material};
+    use crate::ray::Ray;
+    use crate::vec3::{Colour, Point3, Vec3};
+    use rand::Rng;
+
+    #[test]
+    fn white_furnace() {
+        // without absorption every bit of light has to leave the fiber somewhere
+        let hair = Hair::new(Colour::new_z(), 1.55, 0.3, 0.3, 2.0);
+        let mut rng = rand::thread_rng();
+        let mut rec = HitRecord::new();
+        rec.norm = Vec3::new(0.0, 0.0, 1.0);
+        rec.dpdu = Vec3::new(1.0, 0.0, 0.0);
+
+        let n = 200_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            rec.v = rng.gen_range(0.0..1.0);
+            let dir = -Vec3::unit_vector(Vec3::new(rng.gen_range(-1.0..1.0), 0.0, 1.0));
+            let mut attenuation = Colour::new_z();
+            let mut scattered = Ray::default();
+            if hair.scatter(Ray::new(Point3::new(0.0, 0.0, 1.0), dir), &rec, &mut attenuation, &mut scattered) {
+                sum += attenuation.y();
+            }
+        }
+        let mean = sum / n as f64;
+        assert!((mean - 1.0).abs() < 0.05, "furnace mean {mean}");
+    }
+}
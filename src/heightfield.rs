@@ -0,0 +1,280 @@
+use crate::hit::{Collide, HitRecord, Hittable, Intersects, Material};
+use crate::mesh::hit_triangle;
+use crate::perlin::Perlin;
+use crate::ray::Ray;
+use crate::texture::read_pgm;
+use crate::vec3::{Point3, Vec3};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+/* A regular grid of heights spanning the xz plane. Rays walk the grid cell by cell
+ *  and only test the two triangles of each cell they pass over, so a terrain of
+ *  millions of samples never has to exist as a triangle mesh.
+ */
+pub struct Heightfield {
+    // number of samples along x and z
+    nx: usize,
+    nz: usize,
+    // normalised heights (0..1), row major, nx samples per row
+    heights: Vec<f64>,
+    // per-sample normals, interpolated across each triangle
+    normals: Vec<Vec3>,
+    // (min, max) world height of each cell, lets the walk skip cells quickly
+    cell_bounds: Vec<(f64, f64)>,
+    // corner of the field at height 0
+    origin: Point3,
+    // world extents along x, y (height of a sample valued 1.0) and z
+    size: Vec3,
+    min_height: f64,
+    max_height: f64,
+    material: Arc<dyn Material + Sync + Send>,
+}
+
+impl Heightfield {
+    pub fn new(
+        nx: usize, nz: usize, heights: Vec<f64>, origin: Point3, size: Vec3,
+        material: Arc<dyn Material + Sync + Send>) -> Self {
+        assert!(nx >= 2 && nz >= 2, "heightfield needs at least 2x2 samples");
+        assert_eq!(heights.len(), nx * nz, "heightfield sample count does not match grid");
+
+        let dx = size.x() / (nx - 1) as f64;
+        let dz = size.z() / (nz - 1) as f64;
+        let h = |i: usize, j: usize| heights[j * nx + i] * size.y();
+
+        // central differences, one sided on the edges
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+                let dhdx = (h(i1, j) - h(i0, j)) / ((i1 - i0) as f64 * dx);
+                let dhdz = (h(i, j1) - h(i, j0)) / ((j1 - j0) as f64 * dz);
+                normals.push(Vec3::unit_vector(Vec3::new(-dhdx, 1.0, -dhdz)));
+            }
+        }
+
+        let mut cell_bounds = Vec::with_capacity((nx - 1) * (nz - 1));
+        for j in 0..nz - 1 {
+            for i in 0..nx - 1 {
+                let corners = [h(i, j), h(i + 1, j), h(i, j + 1), h(i + 1, j + 1)];
+                let lo = corners.iter().cloned().fold(f64::INFINITY, f64::min);
+                let hi = corners.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                cell_bounds.push((origin.y() + lo, origin.y() + hi));
+            }
+        }
+        let min_height = cell_bounds.iter().map(|b| b.0).fold(f64::INFINITY, f64::min);
+        let max_height = cell_bounds.iter().map(|b| b.1).fold(f64::NEG_INFINITY, f64::max);
+
+        Self {
+            nx,
+            nz,
+            heights,
+            normals,
+            cell_bounds,
+            origin,
+            size,
+            min_height,
+            max_height,
+            material,
+        }
+    }
+
+    /* @brief Loads heights from a grayscale PGM (P2 or P5), 16-bit samples included
+     */
+    pub fn from_pgm(
+        path: impl AsRef<Path>, origin: Point3, size: Vec3,
+        material: Arc<dyn Material + Sync + Send>) -> io::Result<Self> {
+        let (nx, nz, heights) = read_pgm(BufReader::new(File::open(path)?))?;
+        if nx < 2 || nz < 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "heightfield image smaller than 2x2"));
+        }
+        Ok(Self::new(nx, nz, heights, origin, size, material))
+    }
+
+    /* @brief Generates heights from fractal perlin noise, `scale` being the number of
+     *  noise lattice cells spanned by the whole field
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_noise(
+        nx: usize, nz: usize, scale: f64, octaves: usize, seed: u64, origin: Point3,
+        size: Vec3, material: Arc<dyn Material + Sync + Send>) -> Self {
+        let perlin = Perlin::new(seed);
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let x = scale * i as f64 / (nx - 1) as f64;
+                let z = scale * j as f64 / (nz - 1) as f64;
+                // fbm stays within -2..2, remap to 0..1
+                heights.push((0.5 + 0.25 * perlin.fbm(x, z, octaves)).clamp(0.0, 1.0));
+            }
+        }
+        Self::new(nx, nz, heights, origin, size, material)
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> (Point3, Vec3) {
+        let dx = self.size.x() / (self.nx - 1) as f64;
+        let dz = self.size.z() / (self.nz - 1) as f64;
+        let k = j * self.nx + i;
+        let p = self.origin + Vec3::new(i as f64 * dx, self.heights[k] * self.size.y(), j as f64 * dz);
+        (p, self.normals[k])
+    }
+
+    // tests both triangles of cell (i, j), returning the closer hit
+    fn hit_cell(&self, ray: &Ray, i: usize, j: usize, t_min: f64, t_max: f64) -> Option<(f64, Vec3)> {
+        let v00 = self.vertex(i, j);
+        let v10 = self.vertex(i + 1, j);
+        let v01 = self.vertex(i, j + 1);
+        let v11 = self.vertex(i + 1, j + 1);
+
+        let mut closest: Option<(f64, Vec3)> = None;
+        for tri in [[v00, v11, v10], [v00, v01, v11]] {
+            let t_hi = closest.map_or(t_max, |c| c.0);
+            if let Some((t, b1, b2)) = hit_triangle(ray, tri[0].0, tri[1].0, tri[2].0, t_min, t_hi) {
+                let norm = (1.0 - b1 - b2) * tri[0].1 + b1 * tri[1].1 + b2 * tri[2].1;
+                closest = Some((t, Vec3::unit_vector(norm)));
+            }
+        }
+        closest
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_record: &mut HitRecord) -> bool {
+        // clip the ray against the bounding box of the field
+        let lo = Point3::new(self.origin.x(), self.min_height, self.origin.z());
+        let hi = Point3::new(self.origin.x() + self.size.x(), self.max_height, self.origin.z() + self.size.z());
+        let (mut t0, mut t1) = (t_min, t_max);
+        for a in 0..3 {
+            let inv_d = 1.0 / ray.direction()[a];
+            let mut ta = (lo[a] - ray.origin()[a]) * inv_d;
+            let mut tb = (hi[a] - ray.origin()[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut ta, &mut tb);
+            }
+            // NaN from a ray lying on a slab face is ignored by max/min
+            t0 = t0.max(ta);
+            t1 = t1.min(tb);
+            if t1 < t0 {
+                return false;
+            }
+        }
+
+        // walk the cells under the ray (2D DDA on the xz grid)
+        let dx = self.size.x() / (self.nx - 1) as f64;
+        let dz = self.size.z() / (self.nz - 1) as f64;
+        let entry = ray.at(t0);
+        let mut i = (((entry.x() - self.origin.x()) / dx).floor().max(0.0) as usize).min(self.nx - 2);
+        let mut j = (((entry.z() - self.origin.z()) / dz).floor().max(0.0) as usize).min(self.nz - 2);
+
+        let (dir, ori) = (ray.direction(), ray.origin());
+        let step_i: isize = if dir.x() > 0.0 { 1 } else { -1 };
+        let step_j: isize = if dir.z() > 0.0 { 1 } else { -1 };
+        let delta_i = if dir.x() != 0.0 { dx / dir.x().abs() } else { f64::INFINITY };
+        let delta_j = if dir.z() != 0.0 { dz / dir.z().abs() } else { f64::INFINITY };
+        let mut next_i = if dir.x() != 0.0 {
+            let edge = self.origin.x() + (i as f64 + if step_i > 0 { 1.0 } else { 0.0 }) * dx;
+            (edge - ori.x()) / dir.x()
+        } else {
+            f64::INFINITY
+        };
+        let mut next_j = if dir.z() != 0.0 {
+            let edge = self.origin.z() + (j as f64 + if step_j > 0 { 1.0 } else { 0.0 }) * dz;
+            (edge - ori.z()) / dir.z()
+        } else {
+            f64::INFINITY
+        };
+
+        let mut t_enter = t0;
+        loop {
+            let t_leave = next_i.min(next_j).min(t1);
+            let (cell_lo, cell_hi) = self.cell_bounds[j * (self.nx - 1) + i];
+            let (y_a, y_b) = (ray.at(t_enter).y(), ray.at(t_leave).y());
+            // only bother with the triangles if the ray passes through the cell's height range
+            if y_a.min(y_b) <= cell_hi && y_a.max(y_b) >= cell_lo {
+                if let Some((t, norm)) = self.hit_cell(ray, i, j, t_min, t1) {
+                    hit_record.t = t;
+                    hit_record.point = ray.at(t);
+                    hit_record.set_face_norm(ray, norm);
+                    // u, v run along x and z over the whole field
+                    hit_record.u = (hit_record.point.x() - self.origin.x()) / self.size.x();
+                    hit_record.v = (hit_record.point.z() - self.origin.z()) / self.size.z();
+                    hit_record.dpdu = self.size.x() * Vec3::unit_vector(Vec3::new(norm.y(), -norm.x(), 0.0));
+                    hit_record.dpdv = self.size.z() * Vec3::unit_vector(Vec3::new(0.0, -norm.z(), norm.y()));
+                    hit_record.material = Some(self.material.clone());
+                    return true;
+                }
+            }
+            if t_leave >= t1 {
+                return false;
+            }
+            if next_i < next_j {
+                let ni = i as isize + step_i;
+                if ni < 0 || ni > self.nx as isize - 2 {
+                    return false;
+                }
+                i = ni as usize;
+                next_i += delta_i;
+            } else {
+                let nj = j as isize + step_j;
+                if nj < 0 || nj > self.nz as isize - 2 {
+                    return false;
+                }
+                j = nj as usize;
+                next_j += delta_j;
+            }
+            t_enter = t_leave;
+        }
+    }
+}
+
+impl Intersects for Heightfield {
+    fn center(&self) -> Point3 {
+        let mid_y = 0.5 * (self.min_height + self.max_height);
+        Point3::new(self.origin.x() + 0.5 * self.size.x(), mid_y, self.origin.z() + 0.5 * self.size.z())
+    }
+
+    fn radius(&self) -> f64 {
+        0.5 * Vec3::new(self.size.x(), self.max_height - self.min_height, self.size.z()).length()
+    }
+}
+
+impl Collide for Heightfield {
+
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::Heightfield;
+    use crate::hit::{HitRecord, Hittable, Lambertian};
+    use crate::ray::Ray;
+    use crate::vec3::{Colour, Point3, Vec3};
+    use std::sync::Arc;
+
+    #[test]
+    fn walks_to_the_right_cell() {
+        let material = Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5)));
+        // flat at height 0 except a single raised sample at (2, 2)
+        let mut heights = vec![0.0; 25];
+        heights[2 * 5 + 2] = 1.0;
+        let field = Heightfield::new(5, 5, heights, Point3::new(0.0, 0.0, 0.0), Vec3::new(4.0, 2.0, 4.0), material);
+
+        let mut rec = HitRecord::new();
+        // straight down onto the flat part
+        let ray = Ray::new(Point3::new(0.5, 5.0, 3.5), Vec3::new(0.0, -1.0, 0.0));
+        assert!(field.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.point().y()).abs() < 1e-9);
+        assert!((rec.norm().y() - 1.0).abs() < 1e-9);
+
+        // grazing ray along z at y = 1.5 only meets the peak
+        let ray = Ray::new(Point3::new(2.0, 1.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(field.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!(rec.point().z() > 1.0 && rec.point().z() < 2.0);
+
+        // passes above everything
+        let ray = Ray::new(Point3::new(0.0, 2.5, 0.0), Vec3::new(1.0, 0.0, 1.0));
+        assert!(!field.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+    }
+}
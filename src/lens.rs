@@ -0,0 +1,338 @@
+use crate::camera::{frame, View};
+use crate::constants::MM;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use rand::Rng;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/* One row of a lens prescription, front of the lens first, all in mm. A radius of 0
+ *  is the aperture stop, a positive radius bulges toward the scene. The thickness and
+ *  index are of what lies behind the surface, toward the film, 0 or 1 being air.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LensElement {
+    pub radius: f64,
+    pub thickness: f64,
+    pub ior: f64,
+    // diameter of the clear opening
+    pub aperture: f64,
+}
+
+/* @brief Reads a prescription table, a row of radius, thickness, index and aperture
+ *  per surface with # comments, as the patent literature lists them
+ */
+pub fn load(path: impl AsRef<Path>) -> io::Result<Vec<LensElement>> {
+    parse(&fs::read_to_string(path)?)
+}
+
+pub fn parse(text: &str) -> io::Result<Vec<LensElement>> {
+    let mut elements = Vec::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let fields = line
+            .split_whitespace()
+            .map(|s| s.parse::<f64>().map_err(|_| invalid(&format!("bad number {s}"))))
+            .collect::<io::Result<Vec<f64>>>()?;
+        match fields[..] {
+            [] => continue,
+            [radius, thickness, ior, aperture] => elements.push(LensElement { radius, thickness, ior, aperture }),
+            _ => return Err(invalid("expected radius, thickness, index and aperture")),
+        }
+    }
+    if elements.is_empty() {
+        return Err(invalid("no elements"));
+    }
+    Ok(elements)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("lens: {msg}"))
+}
+
+/* A camera that traces each ray through every surface of a real lens design, so the
+ *  lens's own distortion, vignetting, aberrations and focus breathing come with it.
+ *  The film sits at lookfrom, the lens in front of it, and scene units are metres.
+ *  The pupil the rear element presents to each part of the film is found up front
+ *  so rays are only aimed where they can get through.
+ */
+pub struct LensCamera {
+    origin: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    elements: Vec<LensElement>,
+    film_width: f64,
+    film_height: f64,
+    // bounds of the exit pupil seen from along +x at increasing film radii
+    pupils: Vec<Option<Bounds>>,
+    max_pupil_area: f64,
+}
+
+// min and max corners of a rectangle on the rear element's plane
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl Bounds {
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0) * (self.max.1 - self.min.1)
+    }
+}
+
+impl LensCamera {
+    const PUPIL_BINS: usize = 64;
+    const PUPIL_GRID: usize = 64;
+
+    /* @brief film_diagonal in mm, 43.3 being 35mm full frame, and focus_dist from the
+     *  film in scene units. Fails where the lens can't be racked out to focus there.
+     */
+    pub fn new(
+        lookfrom: Point3, lookat: Point3, vup: Vec3, elements: Vec<LensElement>,
+        film_diagonal: f64, aspect_ratio: f64, focus_dist: f64) -> io::Result<Self> {
+        let (u, v, w) = frame(lookfrom, lookat, vup);
+        let film_height = film_diagonal / (aspect_ratio * aspect_ratio + 1.0).sqrt();
+        let mut camera = Self {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            elements,
+            film_width: aspect_ratio * film_height,
+            film_height,
+            pupils: Vec::new(),
+            max_pupil_area: 0.0,
+        };
+        camera.focus(focus_dist / MM)?;
+        camera.pupils = (0..Self::PUPIL_BINS).map(|i| camera.exit_pupil(i)).collect();
+        camera.max_pupil_area = camera.pupils.iter().flatten().map(Bounds::area).fold(0.0, f64::max);
+        Ok(camera)
+    }
+
+    fn rear_z(&self) -> f64 {
+        -self.elements[self.elements.len() - 1].thickness
+    }
+
+    fn rear_radius(&self) -> f64 {
+        self.elements[self.elements.len() - 1].aperture / 2.0
+    }
+
+    /* @brief Lens space has the film at z = 0 and the lens toward -z, in mm. Gives
+     *  where the ray leaves the front element and its direction, or None where a
+     *  surface or the stop blocks it or it reflects internally.
+     */
+    fn trace(&self, mut o: Vec3, mut d: Vec3) -> Option<(Vec3, Vec3)> {
+        let mut z = 0.0;
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            z -= element.thickness;
+            let t = if element.radius == 0.0 {
+                (z - o.z()) / d.z()
+            } else {
+                sphere(element.radius, z + element.radius, o, d)?
+            };
+            if t <= 0.0 {
+                return None;
+            }
+            let hit = o + t * d;
+            let r = element.aperture / 2.0;
+            if hit.x() * hit.x() + hit.y() * hit.y() > r * r {
+                return None;
+            }
+            o = hit;
+            if element.radius != 0.0 {
+                let centre = Vec3::new(0.0, 0.0, z + element.radius);
+                let mut n = Vec3::unit_vector(hit - centre);
+                if Vec3::dot(n, d) > 0.0 {
+                    n = -n;
+                }
+                let air = |ior: f64| if ior == 0.0 { 1.0 } else { ior };
+                let beyond = if i > 0 { air(self.elements[i - 1].ior) } else { 1.0 };
+                d = refract(Vec3::unit_vector(d), n, air(element.ior) / beyond)?;
+            }
+        }
+        Some((o, d))
+    }
+
+    /* @brief Where a ray leaving the middle of the film at a shallow angle crosses the
+     *  axis again in front of the lens, in mm from the film, with the rear element gap
+     */
+    fn conjugate(&mut self, gap: f64) -> f64 {
+        let last = self.elements.len() - 1;
+        self.elements[last].thickness = gap;
+        let target = Vec3::new(0.05 * self.rear_radius(), 0.0, self.rear_z());
+        match self.trace(Vec3::new(0.0, 0.0, 0.0), target) {
+            // converging back to the axis, in front of the lens
+            Some((o, d)) if o.x() * d.x() < 0.0 => -(o.z() - o.x() / d.x() * d.z()),
+            _ => f64::INFINITY,
+        }
+    }
+
+    // racks the lens out from the film until distance (mm) is sharp
+    fn focus(&mut self, distance: f64) -> io::Result<()> {
+        let (mut lo, mut hi) = (1e-3, self.elements[self.elements.len() - 1].thickness.max(1.0));
+        if self.conjugate(lo) <= distance {
+            return Err(invalid(&format!("can't focus as far as {distance} mm")));
+        }
+        while self.conjugate(hi) > distance {
+            hi *= 2.0;
+            if hi > 1e4 {
+                return Err(invalid(&format!("can't focus as close as {distance} mm")));
+            }
+        }
+        // further from the film brings the focus in
+        for _ in 0..64 {
+            let mid = 0.5 * (lo + hi);
+            if self.conjugate(mid) > distance {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        let last = self.elements.len() - 1;
+        self.elements[last].thickness = 0.5 * (lo + hi);
+        Ok(())
+    }
+
+    // the rectangle of the rear element that film points in bin can see through
+    fn exit_pupil(&self, bin: usize) -> Option<Bounds> {
+        let half_diagonal = 0.5 * self.film_width.hypot(self.film_height);
+        let (r0, r1) = (bin as f64 / Self::PUPIL_BINS as f64, (bin + 1) as f64 / Self::PUPIL_BINS as f64);
+        let extent = 1.5 * self.rear_radius();
+        let step = 2.0 * extent / Self::PUPIL_GRID as f64;
+        let mut bounds: Option<Bounds> = None;
+        for k in 0..4 {
+            let x = half_diagonal * (r0 + (r1 - r0) * (k as f64 + 0.5) / 4.0);
+            let film = Vec3::new(x, 0.0, 0.0);
+            for i in 0..Self::PUPIL_GRID {
+                for j in 0..Self::PUPIL_GRID {
+                    let (px, py) = (-extent + (i as f64 + 0.5) * step, -extent + (j as f64 + 0.5) * step);
+                    if self.trace(film, Vec3::new(px, py, self.rear_z()) - film).is_none() {
+                        continue;
+                    }
+                    bounds = Some(match bounds {
+                        Some(b) => Bounds { min: (b.min.0.min(px), b.min.1.min(py)), max: (b.max.0.max(px), b.max.1.max(py)) },
+                        None => Bounds { min: (px, py), max: (px, py) },
+                    });
+                }
+            }
+        }
+        // out by a cell either side to cover what fell between the samples
+        bounds.map(|b| Bounds {
+            min: (b.min.0 - step, b.min.1 - step),
+            max: (b.max.0 + step, b.max.1 + step),
+        })
+    }
+}
+
+impl View for LensCamera {
+    // None where the lens vignettes, so the corners come out as dark as they should
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let mut rng = rand::thread_rng();
+        // the lens turns the image over, so the film is read back to front
+        let film = Vec3::new((0.5 - s) * self.film_width, (0.5 - t) * self.film_height, 0.0);
+        let r = film.x().hypot(film.y());
+        let half_diagonal = 0.5 * self.film_width.hypot(self.film_height);
+        let bin = ((r / half_diagonal * Self::PUPIL_BINS as f64) as usize).min(Self::PUPIL_BINS - 1);
+        let bounds = self.pupils[bin]?;
+
+        let (x, y) = (rng.gen_range(bounds.min.0..bounds.max.0), rng.gen_range(bounds.min.1..bounds.max.1));
+        // the bounds were found along +x, turned round to where the film point is
+        let (cos, sin) = if r > 0.0 { (film.x() / r, film.y() / r) } else { (1.0, 0.0) };
+        let pupil = Vec3::new(cos * x - sin * y, sin * x + cos * y, self.rear_z());
+        let d = Vec3::unit_vector(pupil - film);
+        // irradiance falls with the pupil's size and cos^4 off axis, and every sample
+        //  counts the same, so the difference is made up by dropping some
+        if rng.gen_range(0.0..1.0) > bounds.area() / self.max_pupil_area * d.z().powi(4) {
+            return None;
+        }
+        let (o, d) = self.trace(film, d)?;
+        let to_world = |p: Vec3| p.x() * self.u + p.y() * self.v + p.z() * self.w;
+        Some(Ray::new(self.origin + MM * to_world(o), to_world(d)))
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        self.film_width / self.film_height
+    }
+}
+
+// the nearer or further crossing, whichever the surface's curvature says
+fn sphere(radius: f64, z_centre: f64, o: Vec3, d: Vec3) -> Option<f64> {
+    let oc = o - Vec3::new(0.0, 0.0, z_centre);
+    let a = d.length_squared();
+    let half_b = Vec3::dot(oc, d);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (t0, t1) = ((-half_b - root) / a, (-half_b + root) / a);
+    let closer = (d.z() > 0.0) != (radius < 0.0);
+    Some(if closer { t0.min(t1) } else { t0.max(t1) })
+}
+
+// None on total internal reflection, n facing back against d
+fn refract(d: Vec3, n: Vec3, eta: f64) -> Option<Vec3> {
+    let cos = Vec3::dot(-d, n).min(1.0);
+    if eta * eta * (1.0 - cos * cos) >= 1.0 {
+        return None;
+    }
+    Some(Vec3::refract(d, n, eta))
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{parse, LensCamera};
+    use crate::camera::View;
+    use crate::vec3::{Point3, Vec3};
+
+    // double gauss 50mm f/2, US patent 2,673,491
+    const DGAUSS: &str = "
+        # radius  thickness  index  aperture
+        29.475    3.76       1.67   25.2
+        84.83     0.12       1      25.2
+        19.275    4.025      1.67   23
+        40.77     3.275      1.699  23
+        12.75     5.705      1      18
+        0         4.5        0      17.1
+        -14.495   1.18       1.603  17
+        40.77     6.065      1.658  20
+        -20.385   0.19       1      20
+        437.065   3.22       1.717  20
+        -39.73    5          1      20
+    ";
+
+    #[test]
+    fn focuses_where_asked() {
+        let elements = parse(DGAUSS).unwrap();
+        assert_eq!(elements.len(), 11);
+        assert!(parse("1 2 3").is_err());
+
+        let (from, at, up) = (Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        let cam = LensCamera::new(from, at, up, elements.clone(), 43.3, 1.5, 3.0).unwrap();
+        assert!(LensCamera::new(from, at, up, elements, 43.3, 1.5, 0.01).is_err());
+
+        // every ray from the middle of the film passes close by the point in focus
+        let focus = Point3::new(0.0, 0.0, -3.0);
+        let mut traced = 0;
+        for _ in 0..200 {
+            let Some(ray) = cam.get_ray(0.5, 0.5) else { continue };
+            let t = (focus.z() - ray.origin().z()) / ray.direction().z();
+            let miss = ray.origin() + t * ray.direction() - focus;
+            assert!(miss.length() < 5e-3, "{miss:?}");
+            traced += 1;
+        }
+        assert!(traced > 100, "{traced}");
+
+        // the left of the frame sees the left of the scene
+        let left = (0..100).find_map(|_| cam.get_ray(0.1, 0.5)).unwrap();
+        assert!(left.direction().x() < 0.0);
+        // and the corners are darker than the middle
+        let corner = (0..1000).filter(|_| cam.get_ray(0.0, 0.0).is_some()).count();
+        assert!(corner < traced * 5, "{corner}");
+    }
+}
@@ -3,10 +3,35 @@ use crate::vec3::{Colour, Point3, Vec3};
 pub mod ray;
 use crate::ray::ray_colour;
 pub mod hit;
-use crate::hit::random_scene;
+use crate::hit::{random_scene, HittableList};
 pub mod camera;
 pub mod constants;
-use crate::camera::Camera;
+pub mod perlin;
+pub mod heightfield;
+pub mod bvh;
+pub mod fresnel;
+pub mod curve;
+pub mod hair;
+pub mod texture;
+pub mod mesh;
+pub mod ply;
+pub mod stl;
+pub mod gltf_scene;
+pub mod onb;
+pub mod microfacet;
+pub mod principled;
+pub mod spectrum;
+pub mod normal_map;
+pub mod alpha;
+pub mod subsurface;
+pub mod coated;
+pub mod sheen;
+pub mod aperture;
+pub mod lens;
+use crate::spectrum::Wavelengths;
+use crate::aperture::Aperture;
+use crate::lens::LensCamera;
+use crate::camera::{Camera, CameraSettings, CubeMap, Equirectangular, Fisheye, FisheyeMapping, Stereo, StereoLayout, StereoMode, View};
 
 use indicatif::{style::ProgressStyle, ParallelProgressIterator};
 use rand::distributions::{Distribution, Uniform};
@@ -18,9 +43,41 @@ fn main() -> io::Result<()> {
     // Image
     let aspect_ratio = 3.0 / 2.0;
     let image_width: usize = 1200;
-    let image_height: usize = (image_width as f64 / aspect_ratio) as usize;
     let samples_per_pix: usize = 200;
     let max_depth: usize = 50;
+    // trace a few wavelengths per sample rather than RGB
+    let spectral = std::env::args().any(|arg| arg == "--spectral");
+    // perspective, orthographic, fisheye, equirectangular or cubemap
+    let projection = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--projection=").map(str::to_owned))
+        .unwrap_or_else(|| "perspective".to_owned());
+    // a bladed iris instead of a round one, for the shape of the bokeh
+    let blades = std::env::args().find_map(|arg| arg.strip_prefix("--blades=").and_then(|n| n.parse::<usize>().ok()));
+    // a 70mm lens on full frame at f/2.8 in daylight, in place of vfov and aperture
+    let physical = std::env::args().any(|arg| arg == "--physical");
+    // vertical lens shift as a fraction of the frame, and focus plane tilt in degrees
+    let shift = std::env::args().find_map(|arg| arg.strip_prefix("--shift=").and_then(|x| x.parse::<f64>().ok()));
+    let tilt = std::env::args().find_map(|arg| arg.strip_prefix("--tilt=").and_then(|x| x.parse::<f64>().ok()));
+    // focus on what's under the centre of the frame, or under --autofocus=s,t
+    let autofocus = std::env::args().find_map(|arg| match arg.strip_prefix("--autofocus") {
+        Some("") => Some((0.5, 0.5)),
+        Some(at) => {
+            let (s, t) = at.strip_prefix('=')?.split_once(',')?;
+            Some((s.parse::<f64>().ok()?, t.parse::<f64>().ok()?))
+        }
+        None => None,
+    });
+    // a lens prescription to trace through in place of the thin lens
+    let lens_file = std::env::args().find_map(|arg| arg.strip_prefix("--lens=").map(str::to_owned));
+    // side-by-side or top-bottom pairs, of the perspective or equirectangular views only
+    let stereo = std::env::args().find_map(|arg| match arg.strip_prefix("--stereo=") {
+        Some("side-by-side") => Some(StereoLayout::SideBySide),
+        Some("top-bottom") => Some(StereoLayout::TopBottom),
+        _ => None,
+    });
+
+    // World
+    let world = random_scene();
 
     // Camera
     let lookfrom = Point3::new(13.0, 2.0, 3.0);
@@ -32,25 +89,89 @@ fn main() -> io::Result<()> {
     let vfov = 20.0; 
     let focus_dist = 10.0;
     let aperture = 0.1;
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        vfov,
-        aspect_ratio,
-        aperture,
-        focus_dist,
-    );
-
-    // World
-    let world = random_scene();
+    // eyes a little further apart than a person's, for the scale of the scene
+    let interocular = 0.3;
+    let mut perspective = if physical {
+        let settings = CameraSettings {
+            focal_length: 70.0,
+            f_number: 2.8,
+            focus_dist,
+            shutter: 1.0 / 3200.0,
+            ..CameraSettings::default()
+        };
+        Camera::physical(lookfrom, lookat, vup, &settings)
+    } else {
+        Camera::new(lookfrom, lookat, vup, vfov, aspect_ratio, aperture, focus_dist)
+    };
+    if let Some(blades) = blades {
+        perspective = perspective.with_aperture(Aperture::polygon(blades, 0.0));
+    }
+    if let Some(shift) = shift {
+        perspective = perspective.with_shift(0.0, shift);
+    }
+    if let Some(tilt) = tilt {
+        perspective = perspective.with_tilt(tilt, 0.0);
+    }
+    if let Some((s, t)) = autofocus {
+        perspective = perspective.focus_on(&world, s, t);
+    }
+    // what the other cameras focus and converge at
+    let focus_dist = perspective.focus_dist();
+    if stereo.is_some() {
+        let other = match (projection.as_str(), &lens_file) {
+            ("perspective" | "equirectangular", None) => None,
+            (_, Some(_)) => Some("--lens".to_owned()),
+            (projection, None) => Some(format!("--projection={projection}")),
+        };
+        if let Some(other) = other {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("--stereo can't be combined with {other}")));
+        }
+    }
+    let cam: Box<dyn View + Sync> = match (projection.as_str(), stereo) {
+        ("equirectangular", Some(layout)) => Box::new(Stereo::ods(lookfrom, lookat, vup, interocular, layout)),
+        (_, Some(layout)) => Box::new(Stereo::from_camera(&perspective, interocular, focus_dist, StereoMode::OffAxis, layout)),
+        ("orthographic", _) => Box::new(Camera::orthographic(lookfrom, lookat, vup, 6.0, 4.0)),
+        ("fisheye", _) => Box::new(Fisheye::new(lookfrom, lookat, vup, 180.0, aspect_ratio, FisheyeMapping::Equisolid)),
+        ("equirectangular", _) => Box::new(Equirectangular::new(lookfrom, lookat, vup)),
+        ("cubemap", _) => Box::new(CubeMap::new(lookfrom)),
+        _ => match lens_file {
+            // 35mm full frame film
+            Some(path) => Box::new(LensCamera::new(lookfrom, lookat, vup, lens::load(path)?, 43.3, aspect_ratio, focus_dist)?),
+            None => Box::new(perspective),
+        },
+    };
+    let image_height: usize = (image_width as f64 / cam.aspect_ratio()) as usize;
 
     // Output
     let mut out = io::stdout();
     write!(&mut out, "P3\n{image_width} {image_height}\n255\n")?;
 
-    // setup
     let start = time::Instant::now();
+    let result = render(cam.as_ref(), &world, image_width, image_height, samples_per_pix, max_depth, spectral);
+
+    // prints the file from top to bottom
+    result.iter().for_each(|box_str| {
+        write!(out, "{}", *box_str).unwrap();
+        writeln!(out).unwrap();
+    });
+
+    eprint!("\x1b[2K\rDone in {:#?}\n", start.elapsed());
+    Ok(())
+}
+
+/* @brief Traces every pixel of the image through cam, rows from the top, as the text
+ *  of a PPM body
+ */
+fn render(
+    cam: &(dyn View + Sync),
+    world: &HittableList,
+    image_width: usize,
+    image_height: usize,
+    samples_per_pix: usize,
+    max_depth: usize,
+    spectral: bool,
+) -> Vec<Box<str>> {
+    // setup
     let render = (0..(image_height * image_width))
         .rev()
         .collect::<Vec<usize>>();
@@ -67,7 +188,7 @@ fn main() -> io::Result<()> {
         .par_iter()
         .progress_with_style(pb)
         .map_init(
-            || rand::thread_rng(),
+            rand::thread_rng,
             |mut rng, s| {
                 // height rendering from top to bottom + -> -
                 let j = s / image_width; 
@@ -88,20 +209,19 @@ fn main() -> io::Result<()> {
                     //offset origin by jitter
                     let u = (u_jitter + i as f64) / (image_width - 1) as f64;
                     let v = (v_jitter + j as f64) / (image_height - 1) as f64;
-                    let ray = cam.get_ray(u, v);
-                    pixel_colour += ray_colour(ray, &world, max_depth);
+                    // nothing to see outside the projection
+                    let Some(ray) = cam.get_ray(u, v) else { continue };
+                    if spectral {
+                        let wavelengths = Wavelengths::sample(unif.sample(&mut rng));
+                        let values = ray_colour(ray.with_wavelengths(wavelengths), world, max_depth);
+                        pixel_colour += spectrum::to_rgb(values, wavelengths);
+                    } else {
+                        pixel_colour += ray_colour(ray, world, max_depth);
+                    }
                 }
-                Vec3::colour_to_str(pixel_colour, samples_per_pix)
+                Vec3::colour_to_str(pixel_colour * cam.exposure(), samples_per_pix)
             },
         )
         .collect_into_vec(&mut result);
-
-    // prints the file from top to bottom
-    result.iter().for_each(|box_str| {
-        write!(out, "{}", *box_str).unwrap();
-        write!(out, "\n").unwrap();
-    });
-
-    eprint!("\x1b[2K\rDone in {:#?}\n", start.elapsed());
-    Ok(())
+    result
 }
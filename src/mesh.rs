@@ -0,0 +1,209 @@
+use crate::bvh::{Aabb, Bvh};
+use crate::hit::{Collide, HitRecord, Hittable, Intersects, Lambertian, Material};
+use crate::ray::Ray;
+use crate::texture::VertexColours;
+use crate::vec3::{Colour, Point3, Vec3};
+use std::fmt;
+use std::sync::Arc;
+
+/* Indexed triangles as they come out of the importers, optional attributes are one
+ *  entry per vertex
+ */
+#[derive(Debug, Default, Clone)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<(f64, f64)>>,
+    pub colours: Option<Vec<Colour>>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+impl MeshData {
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(&self.positions)
+    }
+
+    pub fn summary(&self) -> MeshSummary {
+        MeshSummary {
+            vertices: self.positions.len(),
+            triangles: self.indices.len(),
+            normals: self.normals.is_some(),
+            colours: self.colours.is_some(),
+            bounds: self.bounds(),
+        }
+    }
+
+    // drops faces pointing past the vertex list and degenerate ones
+    pub(crate) fn check_indices(&mut self) -> usize {
+        let n = self.positions.len();
+        let before = self.indices.len();
+        self.indices.retain(|f| f.iter().all(|&i| i < n) && f[0] != f[1] && f[1] != f[2] && f[0] != f[2]);
+        before - self.indices.len()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MeshSummary {
+    pub vertices: usize,
+    pub triangles: usize,
+    pub normals: bool,
+    pub colours: bool,
+    pub bounds: Aabb,
+}
+
+impl fmt::Display for MeshSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (lo, hi) = (self.bounds.min, self.bounds.max);
+        write!(f, "{} vertices, {} triangles", self.vertices, self.triangles)?;
+        if self.normals {
+            write!(f, ", normals")?;
+        }
+        if self.colours {
+            write!(f, ", vertex colours")?;
+        }
+        write!(
+            f,
+            ", bounds ({:.3}, {:.3}, {:.3}) to ({:.3}, {:.3}, {:.3})",
+            lo.x(), lo.y(), lo.z(), hi.x(), hi.y(), hi.z()
+        )
+    }
+}
+
+pub struct TriangleMesh {
+    data: Arc<MeshData>,
+    bvh: Bvh,
+    material: Arc<dyn Material + Sync + Send>,
+}
+
+impl TriangleMesh {
+    pub fn new(data: MeshData, material: Arc<dyn Material + Sync + Send>) -> Self {
+        Self::from_shared(Arc::new(data), material)
+    }
+
+    pub fn from_shared(data: Arc<MeshData>, material: Arc<dyn Material + Sync + Send>) -> Self {
+        let bounds: Vec<Aabb> = data
+            .indices
+            .iter()
+            .map(|f| Aabb::from_points(&[data.positions[f[0]], data.positions[f[1]], data.positions[f[2]]]))
+            .collect();
+        let bvh = Bvh::new(&bounds);
+        Self { data, bvh, material }
+    }
+
+    /* @brief Diffuse mesh coloured by its own vertex colours, falls back to grey
+     *  when the file had none
+     */
+    pub fn with_vertex_colours(data: MeshData) -> Self {
+        let data = Arc::new(data);
+        let material: Arc<dyn Material + Sync + Send> = match VertexColours::new(data.clone()) {
+            Some(texture) => Arc::new(Lambertian::textured(Arc::new(texture))),
+            None => Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5))),
+        };
+        Self::from_shared(data, material)
+    }
+
+    pub fn data(&self) -> &Arc<MeshData> {
+        &self.data
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hit_record: &mut HitRecord) -> bool {
+        let data = &self.data;
+        let mut closest: Option<(usize, f64, f64, f64)> = None;
+        let hit_any = self.bvh.hit(ray, t_min, t_max, |i, t_hi| {
+            let [i0, i1, i2] = data.indices[i];
+            let (t, b1, b2) = hit_triangle(ray, data.positions[i0], data.positions[i1], data.positions[i2], t_min, t_hi)?;
+            closest = Some((i, t, b1, b2));
+            Some(t)
+        });
+        let (face, t, b1, b2) = match closest {
+            Some(c) if hit_any => c,
+            _ => return false,
+        };
+
+        let [i0, i1, i2] = data.indices[face];
+        let (p0, p1, p2) = (data.positions[i0], data.positions[i1], data.positions[i2]);
+        let b0 = 1.0 - b1 - b2;
+        let geometric = Vec3::unit_vector(Vec3::cross(p1 - p0, p2 - p0));
+        let norm = match &data.normals {
+            Some(n) => {
+                let shading = b0 * n[i0] + b1 * n[i1] + b2 * n[i2];
+                if shading.near_zero() { geometric } else { Vec3::unit_vector(shading) }
+            }
+            None => geometric,
+        };
+
+        hit_record.t = t;
+        hit_record.point = ray.at(t);
+        hit_record.set_face_norm(ray, norm);
+        hit_record.face = face;
+        hit_record.bary = Vec3::new(b0, b1, b2);
+        match &data.uvs {
+            Some(uv) => {
+                let (uv0, uv1, uv2) = (uv[i0], uv[i1], uv[i2]);
+                hit_record.u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+                hit_record.v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+                // solve the edge equations for how p changes with u and v
+                let (du1, du2) = (uv1.0 - uv0.0, uv2.0 - uv0.0);
+                let (dv1, dv2) = (uv1.1 - uv0.1, uv2.1 - uv0.1);
+                let det = du1 * dv2 - dv1 * du2;
+                (hit_record.dpdu, hit_record.dpdv) = if det.abs() > 1e-12 {
+                    ((dv2 * (p1 - p0) - dv1 * (p2 - p0)) / det, (du1 * (p2 - p0) - du2 * (p1 - p0)) / det)
+                } else {
+                    (p1 - p0, p2 - p0)
+                };
+            }
+            None => {
+                hit_record.u = b1;
+                hit_record.v = b2;
+                hit_record.dpdu = p1 - p0;
+                hit_record.dpdv = p2 - p0;
+            }
+        }
+        hit_record.material = Some(self.material.clone());
+        true
+    }
+}
+
+impl Intersects for TriangleMesh {
+    fn center(&self) -> Point3 {
+        self.bvh.bounds().centroid()
+    }
+
+    fn radius(&self) -> f64 {
+        0.5 * self.bvh.bounds().extent().length()
+    }
+}
+
+impl Collide for TriangleMesh {
+
+}
+
+/* @brief Moller-Trumbore, returns t and the barycentric weights of p1 and p2
+ */
+pub(crate) fn hit_triangle(ray: &Ray, p0: Point3, p1: Point3, p2: Point3, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+    let e1 = p1 - p0;
+    let e2 = p2 - p0;
+    let pvec = Vec3::cross(ray.direction(), e2);
+    let det = Vec3::dot(e1, pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin() - p0;
+    let b1 = Vec3::dot(tvec, pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = Vec3::cross(tvec, e1);
+    let b2 = Vec3::dot(ray.direction(), qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = Vec3::dot(e2, qvec) * inv_det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
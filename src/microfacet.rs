@@ -0,0 +1,420 @@
+use crate::constants::pi;
+use crate::fresnel::{fr_conductor, fr_dielectric, ThinFilm};
+use crate::hit::{HitRecord, Material};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::spectrum::Wavelengths;
+use crate::vec3::{Colour, Vec3};
+use rand::Rng;
+
+/* Trowbridge-Reitz (GGX) distribution of microfacet normals. Everything here works
+ *  in the local shading frame, normal along +z and the x roughness along the tangent.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    /* @brief Roughness in 0..1 is squared into alpha, which makes it roughly
+     *  perceptually linear
+     */
+    pub fn new(roughness_x: f64, roughness_y: f64) -> Self {
+        let alpha = |r: f64| (r.clamp(0.0, 1.0) * r.clamp(0.0, 1.0)).max(1e-4);
+        Self { alpha_x: alpha(roughness_x), alpha_y: alpha(roughness_y) }
+    }
+
+    // close enough to a mirror that sampling the lobe is pointless
+    pub fn effectively_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    // density of microfacet normals
+    pub fn d(&self, wm: Vec3) -> f64 {
+        let cos2 = wm.z() * wm.z();
+        let sin2 = (1.0 - cos2).max(0.0);
+        if cos2 <= 0.0 {
+            return 0.0;
+        }
+        let tan2 = sin2 / cos2;
+        let (cos2_phi, sin2_phi) = cos2_sin2_phi(wm);
+        let e = tan2 * (cos2_phi / (self.alpha_x * self.alpha_x) + sin2_phi / (self.alpha_y * self.alpha_y));
+        1.0 / (pi * self.alpha_x * self.alpha_y * cos2 * cos2 * (1.0 + e) * (1.0 + e))
+    }
+
+    pub fn lambda(&self, w: Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 <= 0.0 {
+            return 0.0;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        let (cos2_phi, sin2_phi) = cos2_sin2_phi(w);
+        let alpha2 = cos2_phi * self.alpha_x * self.alpha_x + sin2_phi * self.alpha_y * self.alpha_y;
+        ((1.0 + alpha2 * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    // smith masking of one direction
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // height correlated masking-shadowing
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // density of normals visible from w (facing it), which is also the pdf of sample_wm
+    pub fn pdf(&self, w: Vec3, wm: Vec3) -> f64 {
+        if w.z() == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z().abs() * self.d(wm) * Vec3::dot(w, wm).max(0.0)
+    }
+
+    /* @brief Samples a normal from the distribution of normals visible from w
+     *  (Heitz 2018), u1 and u2 uniform in 0..1
+     */
+    pub fn sample_wm(&self, w: Vec3, u1: f64, u2: f64) -> Vec3 {
+        // stretch into the hemisphere configuration
+        let mut wh = Vec3::unit_vector(Vec3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()));
+        if wh.z() < 0.0 {
+            wh = -wh;
+        }
+        let t1 = if wh.z() < 0.99999 {
+            Vec3::unit_vector(Vec3::cross(Vec3::new(0.0, 0.0, 1.0), wh))
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(wh, t1);
+
+        // uniform disk point warped onto the visible half
+        let r = u1.sqrt();
+        let phi = 2.0 * pi * u2;
+        let px = r * phi.cos();
+        let h = (1.0 - px * px).max(0.0).sqrt();
+        let s = 0.5 * (1.0 + wh.z());
+        let py = (1.0 - s) * h + s * r * phi.sin();
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+        let nh = px * t1 + py * t2 + pz * wh;
+
+        // and back out of it
+        Vec3::unit_vector(Vec3::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(1e-6)))
+    }
+}
+
+fn cos2_sin2_phi(w: Vec3) -> (f64, f64) {
+    let sin2_theta = w.x() * w.x() + w.y() * w.y();
+    if sin2_theta <= 0.0 {
+        return (1.0, 0.0);
+    }
+    (w.x() * w.x() / sin2_theta, w.y() * w.y() / sin2_theta)
+}
+
+/* Rough metal. Reflection comes from a sampled visible microfacet and is weighted
+ *  by the complex fresnel term and smith masking, so energy is only lost where the
+ *  microsurface genuinely shadows itself and rays never leave below the surface.
+ */
+pub struct Conductor {
+    eta: Colour,
+    k: Colour,
+    distrib: TrowbridgeReitz,
+    film: Option<ThinFilm>,
+}
+
+impl Conductor {
+    pub fn new(eta: Colour, k: Colour, roughness: f64) -> Self {
+        Self::anisotropic(eta, k, roughness, roughness)
+    }
+
+    // roughness_u runs along dpdu of the surface, roughness_v across it
+    pub fn anisotropic(eta: Colour, k: Colour, roughness_u: f64, roughness_v: f64) -> Self {
+        Self { eta, k, distrib: TrowbridgeReitz::new(roughness_u, roughness_v), film: None }
+    }
+
+    // oxide layers or tempering colours on the metal
+    pub fn with_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    // fresnel term and, for a film in spectral mode, the wavelengths left to carry on
+    fn fresnel(&self, ray: &Ray, cos_theta: f64) -> (Colour, Option<Wavelengths>) {
+        match self.film {
+            Some(film) => film.reflectance_for(ray, cos_theta, 1.0, self.eta, self.k),
+            None => (fr_conductor(cos_theta, self.eta, self.k), None),
+        }
+    }
+
+    // complex indices sampled at roughly 650, 550 and 450nm
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(Colour::new(0.143, 0.374, 1.442), Colour::new(3.983, 2.385, 1.603), roughness)
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(Colour::new(0.200, 0.924, 1.102), Colour::new(3.912, 2.452, 2.142), roughness)
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::new(Colour::new(1.657, 0.880, 0.521), Colour::new(9.224, 6.270, 4.837), roughness)
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::new(Colour::new(0.155, 0.117, 0.138), Colour::new(4.828, 3.122, 2.147), roughness)
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray: Ray, record: &HitRecord, attenuation: &mut Colour, scattered: &mut Ray) -> bool {
+        let frame = Onb::from_w_u(record.norm(), record.dpdu());
+        let wo = frame.to_local(-Vec3::unit_vector(ray.direction()));
+        if wo.z() <= 0.0 {
+            return false;
+        }
+
+        if self.distrib.effectively_smooth() {
+            let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+            let (f, wavelengths) = self.fresnel(&ray, wo.z());
+            *attenuation = f;
+            *scattered = Ray::new(record.point(), frame.local(wi));
+            if let Some(w) = wavelengths {
+                *scattered = std::mem::take(scattered).with_wavelengths(w);
+            }
+            return true;
+        }
+
+        let mut rng = rand::thread_rng();
+        let wm = self.distrib.sample_wm(wo, rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0));
+        let wi = Vec3::reflect(-wo, wm);
+        if wi.z() <= 0.0 {
+            // bounced into the microsurface, that light is shadowed
+            return false;
+        }
+        // f cos / pdf with the visible normal pdf reduces to F G2 / G1
+        let weight = self.distrib.g(wo, wi) / self.distrib.g1(wo);
+        let (f, wavelengths) = self.fresnel(&ray, Vec3::dot(wo, wm));
+        *attenuation = weight * f;
+        *scattered = Ray::new(record.point(), frame.local(wi));
+        if let Some(w) = wavelengths {
+            *scattered = std::mem::take(scattered).with_wavelengths(w);
+        }
+        true
+    }
+}
+
+/* Frosted glass. Each hit samples a visible microfacet and then reflects or refracts
+ *  through it with the exact dielectric fresnel term as the odds; the fresnel factors
+ *  cancel against those odds so only the masking ratio is left as the weight.
+ *  Media are handled as Dielectric does: rays refracting in carry the absorption,
+ *  rays refracting out are back in clear air and reflections stay where they were.
+ */
+pub struct RoughDielectric {
+    ir: f64,
+    // beer-lambert absorption per unit length inside, zero for clear glass
+    absorption: Colour,
+    distrib: TrowbridgeReitz,
+}
+
+impl RoughDielectric {
+    pub fn new(ir: f64, roughness: f64) -> Self {
+        Self::absorbing(ir, roughness, Colour::new_z())
+    }
+
+    // frosted coloured glass absorbing `absorption` per unit of distance travelled inside
+    pub fn absorbing(ir: f64, roughness: f64, absorption: Colour) -> Self {
+        Self { ir, absorption, distrib: TrowbridgeReitz::new(roughness, roughness) }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, ray: Ray, record: &HitRecord, attenuation: &mut Colour, scattered: &mut Ray) -> bool {
+        // the stored normal always faces the ray, so wo is in the upper hemisphere
+        let frame = Onb::from_w(record.norm());
+        let wo = frame.to_local(-Vec3::unit_vector(ray.direction()));
+        if wo.z() <= 0.0 {
+            return false;
+        }
+        // index on the far side over the index on this side
+        let eta = if record.front_face { self.ir } else { 1.0 / self.ir };
+        let mut rng = rand::thread_rng();
+
+        let wm = if self.distrib.effectively_smooth() {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            self.distrib.sample_wm(wo, rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0))
+        };
+        let cos_o = Vec3::dot(wo, wm);
+        let reflect = fr_dielectric(cos_o, eta) > rng.gen_range(0.0..1.0);
+        let wi = if reflect {
+            Vec3::reflect(-wo, wm)
+        } else {
+            Vec3::refract(-wo, wm, 1.0 / eta)
+        };
+        // reflections have to stay above the macro surface, refractions below it
+        if (wi.z() > 0.0) != reflect {
+            return false;
+        }
+
+        let weight = if self.distrib.effectively_smooth() {
+            1.0
+        } else {
+            self.distrib.g(wo, wi) / self.distrib.g1(wo)
+        };
+        *attenuation = Colour::new(weight, weight, weight);
+        *scattered = Ray::new(record.point(), frame.local(wi));
+        if !reflect {
+            let absorption = if record.front_face { self.absorption } else { Colour::new_z() };
+            *scattered = std::mem::take(scattered).with_absorption(absorption);
+        }
+        true
+    }
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{RoughDielectric, TrowbridgeReitz};
+    use crate::hit::{Dielectric, HitRecord, Material};
+    use crate::ray::Ray;
+    use crate::vec3::{Colour, Vec3};
+    use rand::Rng;
+
+    // a hit on the z = 0 plane, the normal facing the incoming ray
+    fn hit(front_face: bool) -> HitRecord {
+        let mut record = HitRecord::new();
+        record.norm = Vec3::new(0.0, 0.0, 1.0);
+        record.front_face = front_face;
+        record
+    }
+
+    // arriving from above at theta degrees off the normal
+    fn incoming(theta: f64) -> Ray {
+        let theta = theta.to_radians();
+        Ray::new(Vec3::new(-theta.sin(), 0.0, theta.cos()), Vec3::new(theta.sin(), 0.0, -theta.cos()))
+    }
+
+    // (weight, direction) of a scatter, no weight when the path was cut
+    fn scatter(material: &dyn Material, ray: Ray, record: &HitRecord) -> (f64, Ray) {
+        let (mut attenuation, mut scattered) = (Colour::new_z(), Ray::default());
+        if !material.scatter(ray, record, &mut attenuation, &mut scattered) {
+            return (0.0, scattered);
+        }
+        assert!((attenuation.x() - attenuation.y()).abs() < 1e-12 && (attenuation.y() - attenuation.z()).abs() < 1e-12);
+        (attenuation.x(), scattered)
+    }
+
+    #[test]
+    fn visible_normals_match_their_pdf() {
+        // midpoint rule over the hemisphere of normals (d omega = dz dphi), and sampled
+        // normals have to face w
+        let mut rng = rand::thread_rng();
+        for distrib in [TrowbridgeReitz::new(0.5, 0.5), TrowbridgeReitz::new(0.4, 0.8)] {
+            let w = Vec3::unit_vector(Vec3::new(0.4, -0.2, 0.7));
+            let (nz, nphi) = (2000, 200);
+            let (dz, dphi) = (1.0 / nz as f64, 2.0 * std::f64::consts::PI / nphi as f64);
+            let mut integral = 0.0;
+            for i in 0..nz {
+                let z = (i as f64 + 0.5) * dz;
+                let r = (1.0 - z * z).sqrt();
+                for j in 0..nphi {
+                    let phi = (j as f64 + 0.5) * dphi;
+                    integral += distrib.pdf(w, Vec3::new(r * phi.cos(), r * phi.sin(), z)) * dz * dphi;
+                }
+            }
+            assert!((integral - 1.0).abs() < 0.01, "pdf integrates to {integral}");
+
+            for _ in 0..10_000 {
+                let s = distrib.sample_wm(w, rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0));
+                assert!(s.z() > 0.0 && Vec3::dot(s, w) > -1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn rough_glass_loses_only_to_masking() {
+        // a white furnace: no sample carries more than it was given, and what goes
+        //  missing is what the microsurface shadows, more the rougher it is
+        let n = 20_000;
+        for theta in [0.0, 45.0, 75.0] {
+            for front_face in [true, false] {
+                let record = hit(front_face);
+                let mut last = 1.0;
+                for roughness in [0.1, 0.3, 0.6] {
+                    let glass = RoughDielectric::new(1.5, roughness);
+                    let mut total = 0.0;
+                    for _ in 0..n {
+                        let (weight, _) = scatter(&glass, incoming(theta), &record);
+                        assert!((0.0..=1.0 + 1e-9).contains(&weight));
+                        total += weight;
+                    }
+                    let mean = total / n as f64;
+                    assert!(mean <= last + 0.01 && mean > if roughness < 0.2 { 0.99 } else { 0.7 }, "{roughness} {theta} {mean}");
+                    last = mean;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_rough_glass_is_dielectric() {
+        let (rough, smooth) = (RoughDielectric::new(1.5, 0.0), Dielectric::new(1.5));
+        // both send the same two directions, reflected or refracted
+        for front_face in [true, false] {
+            let record = hit(front_face);
+            let d = incoming(30.0).direction();
+            let reflect = Vec3::reflect(d, record.norm());
+            let refract = Vec3::unit_vector(Vec3::refract(d, record.norm(), if front_face { 1.0 / 1.5 } else { 1.5 }));
+            for _ in 0..100 {
+                for material in [&rough as &dyn Material, &smooth] {
+                    let (weight, ray) = scatter(material, incoming(30.0), &record);
+                    let out = Vec3::unit_vector(ray.direction());
+                    assert_eq!(weight, 1.0);
+                    assert!((out - reflect).length() < 1e-9 || (out - refract).length() < 1e-9);
+                }
+            }
+        }
+        // and head on they agree on the odds, 4% reflected
+        let n = 40_000;
+        let reflected = |material: &dyn Material| {
+            (0..n).filter(|_| scatter(material, incoming(0.0), &hit(true)).1.direction().z() > 0.0).count() as f64 / n as f64
+        };
+        let (a, b) = (reflected(&rough), reflected(&smooth));
+        assert!((a - 0.04).abs() < 0.006 && (b - 0.04).abs() < 0.006, "{a} {b}");
+    }
+
+    #[test]
+    fn total_internal_reflection_leaving_glass() {
+        // from inside glass of index 1.5 anything past 41.8 degrees can't get out
+        let record = hit(false);
+        let smooth = RoughDielectric::new(1.5, 0.0);
+        for _ in 0..1000 {
+            let (weight, ray) = scatter(&smooth, incoming(60.0), &record);
+            assert_eq!(weight, 1.0);
+            assert!(ray.direction().z() > 0.0);
+        }
+        // a rough surface lets the odd facet tilted toward the ray through
+        let rough = RoughDielectric::new(1.5, 0.3);
+        let n = 20_000;
+        let escaped = (0..n).filter(|_| scatter(&rough, incoming(60.0), &record).1.direction().z() < 0.0).count();
+        assert!(escaped > 0 && escaped < n / 5, "{escaped} escaped");
+    }
+
+    #[test]
+    fn refraction_carries_the_absorption() {
+        let absorption = Colour::new(0.1, 0.2, 0.3);
+        let glass = RoughDielectric::absorbing(1.5, 0.2, absorption);
+        let mut seen = [false; 2];
+        for front_face in [true, false] {
+            for _ in 0..2000 {
+                let (weight, ray) = scatter(&glass, incoming(0.0), &hit(front_face));
+                if weight > 0.0 && ray.direction().z() < 0.0 {
+                    // in through the front, out through the back into clear air
+                    let expected = if front_face { absorption } else { Colour::new_z() };
+                    assert!((ray.absorption() - expected).near_zero());
+                    seen[front_face as usize] = true;
+                }
+            }
+        }
+        assert_eq!(seen, [true, true]);
+    }
+}
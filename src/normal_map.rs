@@ -0,0 +1,192 @@
+use crate::hit::{HitRecord, Material};
+use crate::ray::Ray;
+use crate::texture::{FloatTexture, ImageTexture, Texture};
+use crate::vec3::{Colour, Vec3};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/* Wraps a material and hands it a hit whose shading normal comes from a tangent
+ *  space normal texture, the usual (0.5, 0.5, 1) blue meaning unperturbed. The
+ *  tangent follows dpdu and the bitangent dpdv.
+ */
+pub struct NormalMap {
+    normals: Arc<dyn Texture + Sync + Send>,
+    material: Arc<dyn Material + Sync + Send>,
+}
+
+impl NormalMap {
+    pub fn new(normals: Arc<dyn Texture + Sync + Send>, material: Arc<dyn Material + Sync + Send>) -> Self {
+        Self { normals, material }
+    }
+
+    // a png or jpeg normal map, read as stored since the channels are directions
+    pub fn load(path: impl AsRef<Path>, material: Arc<dyn Material + Sync + Send>) -> io::Result<Self> {
+        Ok(Self::new(Arc::new(ImageTexture::load_linear(path)?), material))
+    }
+
+    fn shade(&self, record: &HitRecord) -> HitRecord {
+        let n = outward(record);
+        let t = record.dpdu() - Vec3::dot(record.dpdu(), n) * n;
+        if t.near_zero() {
+            return record.clone();
+        }
+        let t = Vec3::unit_vector(t);
+        // mirrored uvs flip the bitangent
+        let b = Vec3::cross(n, t);
+        let b = if Vec3::dot(b, record.dpdv()) < 0.0 { -b } else { b };
+
+        let c = 2.0 * self.normals.value(record) - 1.0;
+        with_shading_normal(record, c.x() * t + c.y() * b + c.z() * n)
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(&self, ray: Ray, record: &HitRecord, attenuation: &mut Colour, scattered: &mut Ray) -> bool {
+        self.material.scatter(ray, &self.shade(record), attenuation, scattered)
+    }
+}
+
+/* Wraps a material and tilts its shading normal as if the surface were displaced
+ *  along it by height times scale, from finite differences of the height in u and v
+ */
+pub struct BumpMap {
+    height: Arc<dyn FloatTexture + Sync + Send>,
+    scale: f64,
+    material: Arc<dyn Material + Sync + Send>,
+}
+
+impl BumpMap {
+    // step in u and v for the finite differences
+    const DELTA: f64 = 5e-4;
+
+    pub fn new(height: Arc<dyn FloatTexture + Sync + Send>, scale: f64, material: Arc<dyn Material + Sync + Send>) -> Self {
+        Self { height, scale, material }
+    }
+
+    fn shade(&self, record: &HitRecord) -> HitRecord {
+        let n = outward(record);
+        let h = self.scale * self.height.value(record);
+
+        // the same hit nudged along u and then along v
+        let mut shifted = record.clone();
+        shifted.point = record.point() + Self::DELTA * record.dpdu();
+        shifted.u = record.u() + Self::DELTA;
+        let dhdu = (self.scale * self.height.value(&shifted) - h) / Self::DELTA;
+        let mut shifted = record.clone();
+        shifted.point = record.point() + Self::DELTA * record.dpdv();
+        shifted.v = record.v() + Self::DELTA;
+        let dhdv = (self.scale * self.height.value(&shifted) - h) / Self::DELTA;
+
+        let dpdu = record.dpdu() + dhdu * n;
+        let dpdv = record.dpdv() + dhdv * n;
+        let bumped = Vec3::cross(dpdu, dpdv);
+        if bumped.near_zero() {
+            return record.clone();
+        }
+        // keep it on the side the geometry faces whichever way u and v run
+        let bumped = if Vec3::dot(bumped, n) < 0.0 { -bumped } else { bumped };
+        let mut shaded = with_shading_normal(record, bumped);
+        shaded.dpdu = dpdu;
+        shaded.dpdv = dpdv;
+        shaded
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(&self, ray: Ray, record: &HitRecord, attenuation: &mut Colour, scattered: &mut Ray) -> bool {
+        self.material.scatter(ray, &self.shade(record), attenuation, scattered)
+    }
+}
+
+// the geometric normal before it was turned to face the ray
+fn outward(record: &HitRecord) -> Vec3 {
+    if record.front_face {
+        record.norm()
+    } else {
+        -record.norm()
+    }
+}
+
+// a copy of the hit with an outward normal swapped in, turned to face the ray again
+fn with_shading_normal(record: &HitRecord, outward: Vec3) -> HitRecord {
+    let mut shaded = record.clone();
+    let n = Vec3::unit_vector(outward);
+    shaded.norm = if record.front_face { n } else { -n };
+    shaded
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{BumpMap, NormalMap};
+    use crate::hit::{HitRecord, Hittable, Lambertian, Sphere};
+    use crate::ray::Ray;
+    use crate::texture::{FloatTexture, SolidColour};
+    use crate::vec3::{Colour, Point3, Vec3};
+    use std::fs;
+    use std::sync::Arc;
+
+    // a ramp rising along u
+    struct Ramp;
+
+    impl FloatTexture for Ramp {
+        fn value(&self, record: &HitRecord) -> f64 {
+            record.u()
+        }
+    }
+
+    #[test]
+    fn perturbs_the_shading_normal() {
+        let mut record = HitRecord::new();
+        record.norm = Vec3::new(0.0, 0.0, 1.0);
+        record.front_face = true;
+        record.dpdu = Vec3::new(2.0, 0.0, 0.0);
+        record.dpdv = Vec3::new(0.0, 2.0, 0.0);
+        let material = Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5)));
+
+        let flat = NormalMap::new(Arc::new(SolidColour::new(Colour::new(0.5, 0.5, 1.0))), material.clone());
+        assert!((flat.shade(&record).norm() - record.norm()).near_zero());
+        let tilted = NormalMap::new(Arc::new(SolidColour::new(Colour::new(1.0, 0.5, 0.5))), material.clone());
+        assert!((tilted.shade(&record).norm() - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+
+        // the height climbs 1 per unit of u over 2 units of distance, so the normal
+        // leans back against u by atan(1/2)
+        let bump = BumpMap::new(Arc::new(Ramp), 1.0, material);
+        let n = bump.shade(&record).norm();
+        assert!((n - Vec3::unit_vector(Vec3::new(-1.0, 0.0, 2.0))).length() < 1e-6);
+        // seen from behind it is flipped along with the geometric normal
+        record.norm = -record.norm;
+        record.front_face = false;
+        assert!((bump.shade(&record).norm() + n).length() < 1e-6);
+    }
+
+    #[test]
+    fn sphere_normal_map_tilts_toward_dpdu() {
+        // 1 by 1 png leaning 45 degrees along the tangent, (0.71, 0, 0.71) once decoded
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 1, 1);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[218, 128, 218]).unwrap();
+        writer.finish().unwrap();
+        let path = std::env::temp_dir().join(format!("ray_tracer_normal_map_{}.png", std::process::id()));
+        fs::write(&path, &png).unwrap();
+        let material = Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5)));
+        let map = NormalMap::load(&path, material.clone()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // seen from +x u runs toward -z, seen from +z it runs toward +x
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material.clone());
+        let bump = BumpMap::new(Arc::new(Ramp), 0.1, material);
+        for (from, tangent) in [(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)), (Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0))] {
+            let mut record = HitRecord::new();
+            assert!(sphere.hit(&Ray::new(5.0 * from, -from), 0.001, f64::INFINITY, &mut record));
+            let n = map.shade(&record).norm();
+            assert!((n - Vec3::unit_vector(from + tangent)).length() < 1e-2, "{n:?}");
+            // and a height rising with u leans the bump normal the other way
+            assert!(Vec3::dot(bump.shade(&record).norm(), tangent) < -1e-3);
+        }
+    }
+}
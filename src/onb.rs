@@ -0,0 +1,60 @@
+use crate::vec3::Vec3;
+
+/* Orthonormal basis with w as the surface normal, so shading code can work in a
+ *  frame where the normal is +z
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    // any tangent will do, e.g. for isotropic materials
+    pub fn from_w(n: Vec3) -> Self {
+        let w = Vec3::unit_vector(n);
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::unit_vector(Vec3::cross(w, a));
+        let u = Vec3::cross(v, w);
+        Self { u, v, w }
+    }
+
+    // u follows the tangent as closely as possible, e.g. dpdu for anisotropic materials
+    pub fn from_w_u(n: Vec3, tangent: Vec3) -> Self {
+        let w = Vec3::unit_vector(n);
+        let t = tangent - Vec3::dot(tangent, w) * w;
+        if t.length_squared() < 1e-12 {
+            return Self::from_w(n);
+        }
+        let u = Vec3::unit_vector(t);
+        let v = Vec3::cross(w, u);
+        Self { u, v, w }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    // local coordinates to world
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    // world to local coordinates
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(a, self.u), Vec3::dot(a, self.v), Vec3::dot(a, self.w))
+    }
+}
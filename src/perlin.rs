@@ -0,0 +1,70 @@
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+const POINT_COUNT: usize = 256;
+
+/* 2D gradient noise, seeded so the same terrain can be regenerated
+ */
+pub struct Perlin {
+    // unit gradients indexed through the permutation tables
+    grads: Vec<(f64, f64)>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let grads = (0..POINT_COUNT)
+            .map(|_| {
+                let angle = rng.gen_range(0.0..std::f64::consts::TAU);
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        let mut perm_x: Vec<usize> = (0..POINT_COUNT).collect();
+        let mut perm_y: Vec<usize> = (0..POINT_COUNT).collect();
+        perm_x.shuffle(&mut rng);
+        perm_y.shuffle(&mut rng);
+        Self { grads, perm_x, perm_y }
+    }
+
+    // noise in roughly -1..1, zero on integer lattice points
+    pub fn noise(&self, x: f64, y: f64) -> f64 {
+        let (fx, fy) = (x.floor(), y.floor());
+        let (u, v) = (x - fx, y - fy);
+        let (i, j) = (fx as i64, fy as i64);
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                let px = self.perm_x[((i + di) & 255) as usize];
+                let py = self.perm_y[((j + dj) & 255) as usize];
+                let g = self.grads[px ^ py];
+                let (wx, wy) = (u - di as f64, v - dj as f64);
+                // hermite smoothing of the interpolation weights
+                let sx = if di == 0 { 1.0 - fade(u) } else { fade(u) };
+                let sy = if dj == 0 { 1.0 - fade(v) } else { fade(v) };
+                accum += sx * sy * (g.0 * wx + g.1 * wy);
+            }
+        }
+        accum * std::f64::consts::SQRT_2
+    }
+
+    // fractal sum of octaves, each at double the frequency and half the amplitude
+    pub fn fbm(&self, x: f64, y: f64, octaves: usize) -> f64 {
+        let mut accum = 0.0;
+        let mut freq = 1.0;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            accum += weight * self.noise(x * freq, y * freq);
+            freq *= 2.0;
+            weight *= 0.5;
+        }
+        accum
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
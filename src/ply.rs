@@ -0,0 +1,381 @@
+use crate::mesh::MeshData;
+use crate::vec3::Vec3;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/* Stanford PLY reader: ascii and binary in either byte order. Vertices keep their
+ *  normals, texture coordinates and colours when present, polygons are fanned
+ *  into triangles and any other elements are skipped.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> io::Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(invalid(&format!("unknown property type {name}"))),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // what a full intensity colour channel reads as
+    fn colour_max(&self) -> f64 {
+        match self {
+            Scalar::U8 | Scalar::I8 => 255.0,
+            Scalar::U16 | Scalar::I16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    props: Vec<Property>,
+}
+
+struct Body<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    format: Format,
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, ty: Scalar) -> io::Result<f64> {
+        if self.format == Format::Ascii {
+            return self.read_token()?.parse::<f64>().map_err(|_| invalid("bad number in body"));
+        }
+        let n = ty.size();
+        let raw = self.bytes.get(self.pos..self.pos + n).ok_or_else(|| invalid("unexpected end of data"))?;
+        self.pos += n;
+        let mut buf = [0u8; 8];
+        buf[..n].copy_from_slice(raw);
+        if self.format == Format::BigEndian {
+            buf[..n].reverse();
+        }
+        Ok(match ty {
+            Scalar::I8 => buf[0] as i8 as f64,
+            Scalar::U8 => buf[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(buf),
+        })
+    }
+
+    fn read_token(&mut self) -> io::Result<&'a str> {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self.pos < self.bytes.len() && !self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(invalid("unexpected end of data"));
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos]).map_err(|_| invalid("non utf8 body"))
+    }
+}
+
+/* @brief Reads a .ply file, MeshData::summary says what was in it
+ */
+pub fn load(path: impl AsRef<Path>) -> io::Result<MeshData> {
+    parse(&fs::read(path)?)
+}
+
+pub fn parse(bytes: &[u8]) -> io::Result<MeshData> {
+    let (format, elements, body_start) = parse_header(bytes)?;
+    let mut body = Body { bytes, pos: body_start, format };
+    let mut mesh = MeshData::default();
+
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut body, element, &mut mesh)?,
+            "face" => read_faces(&mut body, element, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    for prop in &element.props {
+                        read_property(&mut body, prop)?;
+                    }
+                }
+            }
+        }
+    }
+
+    // faces that collapse to a line or a point
+    mesh.check_indices();
+    Ok(mesh)
+}
+
+fn parse_header(bytes: &[u8]) -> io::Result<(Format, Vec<Element>, usize)> {
+    const END: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END.len())
+        .position(|w| w == END)
+        .ok_or_else(|| invalid("missing end_header"))?;
+    // the body starts after the line break that follows end_header
+    let mut body_start = end + END.len();
+    while body_start < bytes.len() && bytes[body_start] != b'\n' {
+        body_start += 1;
+    }
+    body_start += 1;
+
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|_| invalid("non utf8 header"))?;
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(invalid("not a ply file"));
+    }
+
+    let mut format = None;
+    let mut elements = Vec::<Element>::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", f, _] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(invalid(&format!("unknown format {f}"))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| invalid("bad element count"))?,
+                props: Vec::new(),
+            }),
+            ["property", "list", count_ty, item_ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("property before element"))?
+                .props
+                .push(Property::List(name.to_string(), Scalar::parse(count_ty)?, Scalar::parse(item_ty)?)),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("property before element"))?
+                .props
+                .push(Property::Scalar(name.to_string(), Scalar::parse(ty)?)),
+            // comment, obj_info and blank lines
+            _ => {}
+        }
+    }
+    let format = format.ok_or_else(|| invalid("missing format line"))?;
+    Ok((format, elements, body_start))
+}
+
+fn read_property(body: &mut Body, prop: &Property) -> io::Result<Vec<f64>> {
+    match prop {
+        Property::Scalar(_, ty) => Ok(vec![body.read(*ty)?]),
+        Property::List(_, count_ty, item_ty) => {
+            let n = body.read(*count_ty)? as usize;
+            (0..n).map(|_| body.read(*item_ty)).collect()
+        }
+    }
+}
+
+fn read_vertices(body: &mut Body, element: &Element, mesh: &mut MeshData) -> io::Result<()> {
+    let slot = |names: &[&str]| {
+        element.props.iter().position(|p| match p {
+            Property::Scalar(n, _) => names.contains(&n.as_str()),
+            Property::List(..) => false,
+        })
+    };
+    let position = [slot(&["x"]), slot(&["y"]), slot(&["z"])];
+    let normal = [slot(&["nx"]), slot(&["ny"]), slot(&["nz"])];
+    let colour = [
+        slot(&["red", "r", "diffuse_red"]),
+        slot(&["green", "g", "diffuse_green"]),
+        slot(&["blue", "b", "diffuse_blue"]),
+    ];
+    let uv = [slot(&["u", "s", "texture_u", "texture_s"]), slot(&["v", "t", "texture_v", "texture_t"])];
+    if position.iter().any(Option::is_none) {
+        return Err(invalid("vertex element without x, y, z"));
+    }
+    let has = |s: &[Option<usize>]| s.iter().all(Option::is_some);
+    let colour_max = colour[0].map_or(1.0, |i| match element.props[i] {
+        Property::Scalar(_, ty) => ty.colour_max(),
+        Property::List(..) => 1.0,
+    });
+
+    let mut normals = Vec::new();
+    let mut colours = Vec::new();
+    let mut uvs = Vec::new();
+    let mut values = vec![0.0; element.props.len()];
+    for _ in 0..element.count {
+        for (k, prop) in element.props.iter().enumerate() {
+            values[k] = match prop {
+                Property::Scalar(_, ty) => body.read(*ty)?,
+                Property::List(..) => read_property(body, prop).map(|_| 0.0)?,
+            };
+        }
+        let get = |s: [Option<usize>; 3]| Vec3::new(values[s[0].unwrap()], values[s[1].unwrap()], values[s[2].unwrap()]);
+        mesh.positions.push(get(position));
+        if has(&normal) {
+            normals.push(get(normal));
+        }
+        if has(&colour) {
+            // stored gamma encoded, square it back to linear to match the output's gamma 2
+            let c = get(colour) / colour_max;
+            colours.push(c * c);
+        }
+        if has(&uv) {
+            uvs.push((values[uv[0].unwrap()], values[uv[1].unwrap()]));
+        }
+    }
+    mesh.normals = has(&normal).then_some(normals);
+    mesh.colours = has(&colour).then_some(colours);
+    mesh.uvs = has(&uv).then_some(uvs);
+    Ok(())
+}
+
+fn read_faces(body: &mut Body, element: &Element, mesh: &mut MeshData) -> io::Result<()> {
+    let list = element.props.iter().position(|p| {
+        matches!(p, Property::List(n, _, _) if n == "vertex_indices" || n == "vertex_index")
+    });
+    let list = list.ok_or_else(|| invalid("face element without vertex_indices"))?;
+    let vertices = mesh.positions.len();
+    let index = |v: f64| {
+        if v >= 0.0 && v.fract() == 0.0 && (v as usize) < vertices {
+            Ok(v as usize)
+        } else {
+            Err(invalid(&format!("bad vertex index {v}")))
+        }
+    };
+    for _ in 0..element.count {
+        for (k, prop) in element.props.iter().enumerate() {
+            let values = read_property(body, prop)?;
+            if k != list {
+                continue;
+            }
+            let face = values.into_iter().map(index).collect::<io::Result<Vec<usize>>>()?;
+            // fan triangulation, fine for the convex polygons scanners write
+            for i in 1..face.len().saturating_sub(1) {
+                mesh.indices.push([face[0], face[i], face[i + 1]]);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("ply: {msg}"))
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::parse;
+
+    #[test]
+    fn ascii_with_colours() {
+        let data = b"ply
+format ascii 1.0
+comment made by hand
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+        let mesh = parse(data).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        let colours = mesh.colours.unwrap();
+        assert_eq!(colours[1].y(), 1.0);
+        assert_eq!(colours[1].x(), 0.0);
+        assert!(mesh.normals.is_none());
+
+        // faces pointing at vertices that aren't there are an error, not vertex 0
+        let header = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+                      property float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n\
+                      0 0 0\n1 0 0\n0 1 0\n";
+        for face in ["3 0 1 -1", "3 0 1 1.5", "3 0 1 3"] {
+            assert!(parse(format!("{header}{face}\n").as_bytes()).is_err(), "{face}");
+        }
+        assert_eq!(parse(format!("{header}3 0 1 2\n").as_bytes()).unwrap().indices, vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn binary_both_endians() {
+        for (format, big) in [("binary_little_endian", false), ("binary_big_endian", true)] {
+            let mut data = format!(
+                "ply\nformat {format} 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+                 property float z\nproperty double nx\nelement face 1\nproperty list uchar uint vertex_indices\n\
+                 element extra 1\nproperty short junk\nend_header\n"
+            )
+            .into_bytes();
+            let f32s = |v: f32| if big { v.to_be_bytes() } else { v.to_le_bytes() };
+            for p in [[0.0f32, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 3.0, -1.5]] {
+                for c in p {
+                    data.extend_from_slice(&f32s(c));
+                }
+                data.extend_from_slice(&if big { 1.0f64.to_be_bytes() } else { 1.0f64.to_le_bytes() });
+            }
+            data.push(3);
+            for i in [0u32, 1, 2] {
+                data.extend_from_slice(&if big { i.to_be_bytes() } else { i.to_le_bytes() });
+            }
+            data.extend_from_slice(&[0, 7]);
+
+            let mesh = parse(&data).unwrap();
+            assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+            assert_eq!(mesh.positions[2].z(), -1.5);
+            assert_eq!(mesh.positions[1].x(), 2.0);
+            // nx alone is not a normal
+            assert!(mesh.normals.is_none());
+        }
+    }
+}
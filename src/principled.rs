@@ -0,0 +1,427 @@
+use crate::constants::pi;
+use crate::fresnel::fr_dielectric;
+use crate::hit::{HitRecord, Material};
+use crate::microfacet::TrowbridgeReitz;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::{FloatTexture, SolidColour, Texture};
+use crate::vec3::{Colour, Vec3};
+use rand::Rng;
+use std::sync::Arc;
+
+type Scalar = Arc<dyn FloatTexture + Sync + Send>;
+
+/* Disney style uber material. A diffuse base (with retro-reflection, a subsurface
+ *  flattening and sheen), a GGX specular that tints toward the base colour as it turns
+ *  metallic, rough glass for transmission and a thin clearcoat. Every input is a
+ *  texture; plain numbers work for the scalar ones, ImageChannel reads them off a map.
+ */
+pub struct Principled {
+    base_colour: Arc<dyn Texture + Sync + Send>,
+    metallic: Scalar,
+    roughness: Scalar,
+    specular: Scalar,
+    sheen: Scalar,
+    clearcoat: Scalar,
+    clearcoat_gloss: Scalar,
+    transmission: Scalar,
+    subsurface: Scalar,
+    ir: Scalar,
+}
+
+impl Principled {
+    // a plain dielectric at roughness 0.5, the other inputs are set with the methods below
+    pub fn new(base_colour: Colour) -> Self {
+        Self::textured(Arc::new(SolidColour::new(base_colour)))
+    }
+
+    pub fn textured(base_colour: Arc<dyn Texture + Sync + Send>) -> Self {
+        Self {
+            base_colour,
+            metallic: Arc::new(0.0),
+            roughness: Arc::new(0.5),
+            specular: Arc::new(0.5),
+            sheen: Arc::new(0.0),
+            clearcoat: Arc::new(0.0),
+            clearcoat_gloss: Arc::new(1.0),
+            transmission: Arc::new(0.0),
+            subsurface: Arc::new(0.0),
+            ir: Arc::new(1.5),
+        }
+    }
+
+    pub fn metallic(mut self, t: impl FloatTexture + Sync + Send + 'static) -> Self {
+        self.metallic = Arc::new(t);
+        self
+    }
+
+    pub fn roughness(mut self, t: impl FloatTexture + Sync + Send + 'static) -> Self {
+        self.roughness = Arc::new(t);
+        self
+    }
+
+    // 0.5 is a normal incidence reflectance of 4%, the usual dielectric
+    pub fn specular(mut self, t: impl FloatTexture + Sync + Send + 'static) -> Self {
+        self.specular = Arc::new(t);
+        self
+    }
+
+    pub fn sheen(mut self, t: impl FloatTexture + Sync + Send + 'static) -> Self {
+        self.sheen = Arc::new(t);
+        self
+    }
+
+    pub fn clearcoat(mut self, t: impl FloatTexture + Sync + Send + 'static) -> Self {
+        self.clearcoat = Arc::new(t);
+        self
+    }
+
+    pub fn clearcoat_gloss(mut self, t: impl FloatTexture + Sync + Send + 'static) -> Self {
+        self.clearcoat_gloss = Arc::new(t);
+        self
+    }
+
+    pub fn transmission(mut self, t: impl FloatTexture + Sync + Send + 'static) -> Self {
+        self.transmission = Arc::new(t);
+        self
+    }
+
+    pub fn subsurface(mut self, t: impl FloatTexture + Sync + Send + 'static) -> Self {
+        self.subsurface = Arc::new(t);
+        self
+    }
+
+    // refractive index used by the transmission lobe
+    pub fn ir(mut self, t: impl FloatTexture + Sync + Send + 'static) -> Self {
+        self.ir = Arc::new(t);
+        self
+    }
+
+    fn lobes(&self, record: &HitRecord) -> Lobes {
+        let unit = |t: &Scalar| t.value(record).clamp(0.0, 1.0);
+        Lobes::new(
+            self.base_colour.value(record),
+            unit(&self.metallic),
+            unit(&self.roughness),
+            unit(&self.specular),
+            unit(&self.sheen),
+            unit(&self.clearcoat),
+            unit(&self.clearcoat_gloss),
+            unit(&self.transmission),
+            unit(&self.subsurface),
+            if record.front_face { self.ir.value(record) } else { 1.0 / self.ir.value(record) },
+        )
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray: Ray, record: &HitRecord, attenuation: &mut Colour, scattered: &mut Ray) -> bool {
+        let frame = Onb::from_w_u(record.norm(), record.dpdu());
+        let wo = frame.to_local(-Vec3::unit_vector(ray.direction()));
+        if wo.z() <= 0.0 {
+            return false;
+        }
+        match self.lobes(record).sample(wo, &mut rand::thread_rng()) {
+            Some((weight, wi)) => {
+                *attenuation = weight;
+                *scattered = Ray::new(record.point(), frame.local(wi));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/* The material's inputs at one hit, with each lobe's share of the bsdf and the odds
+ *  of sampling it. Directions are in the shading frame, normal along +z.
+ */
+struct Lobes {
+    base: Colour,
+    roughness: f64,
+    sheen: f64,
+    subsurface: f64,
+    cspec0: Colour,
+    eta: f64,
+    distrib: TrowbridgeReitz,
+    coat_alpha: f64,
+    // diffuse, specular, transmission, clearcoat
+    weights: [f64; 4],
+    odds: [f64; 4],
+}
+
+impl Lobes {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        base: Colour,
+        metallic: f64,
+        roughness: f64,
+        specular: f64,
+        sheen: f64,
+        clearcoat: f64,
+        clearcoat_gloss: f64,
+        transmission: f64,
+        subsurface: f64,
+        eta: f64,
+    ) -> Self {
+        let dielectric = 1.0 - metallic;
+        let f0 = 0.08 * specular;
+        let cspec0 = dielectric * Colour::new(f0, f0, f0) + metallic * base;
+        let weights = [
+            dielectric * (1.0 - transmission),
+            1.0 - dielectric * transmission,
+            dielectric * transmission,
+            0.25 * clearcoat,
+        ];
+        Self {
+            base,
+            roughness,
+            sheen,
+            subsurface,
+            cspec0,
+            eta,
+            distrib: TrowbridgeReitz::new(roughness, roughness),
+            coat_alpha: 0.1 + (0.001 - 0.1) * clearcoat_gloss,
+            weights,
+            odds: [0.0; 4],
+        }
+    }
+
+    // sampling odds follow a rough guess at how much each lobe reflects toward wo
+    fn set_odds(&mut self, wo: Vec3) -> bool {
+        let approx = [
+            luminance(self.base),
+            luminance(schlick(self.cspec0, wo.z())),
+            1.0,
+            schlick_weight(wo.z()) * 0.96 + 0.04,
+        ];
+        for (p, (w, a)) in self.odds.iter_mut().zip(self.weights.iter().zip(approx)) {
+            *p = w * a;
+        }
+        let total: f64 = self.odds.iter().sum();
+        if total <= 0.0 {
+            return false;
+        }
+        self.odds.iter_mut().for_each(|p| *p /= total);
+        true
+    }
+
+    /* @brief Picks a lobe and samples it, then weighs the direction by the whole bsdf
+     *  over the combined pdf of every lobe that could have produced it
+     */
+    fn sample(&mut self, wo: Vec3, rng: &mut impl Rng) -> Option<(Colour, Vec3)> {
+        if !self.set_odds(wo) {
+            return None;
+        }
+        let pick: f64 = rng.gen_range(0.0..1.0);
+        let (u1, u2) = (rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0));
+        let wi = if pick < self.odds[0] {
+            Vec3::rand_cosine_direction()
+        } else if pick < self.odds[0] + self.odds[1] {
+            Vec3::reflect(-wo, self.distrib.sample_wm(wo, u1, u2))
+        } else if pick < self.odds[0] + self.odds[1] + self.odds[2] {
+            let wm = self.distrib.sample_wm(wo, u1, u2);
+            if fr_dielectric(Vec3::dot(wo, wm), self.eta) <= rng.gen_range(0.0..1.0) {
+                let wi = Vec3::refract(-wo, wm, 1.0 / self.eta);
+                // a refraction that ends up above the macro surface is lost
+                return if wi.z() < 0.0 { self.weigh(wo, wi) } else { None };
+            }
+            Vec3::reflect(-wo, wm)
+        } else {
+            Vec3::reflect(-wo, sample_gtr1(self.coat_alpha, u1, u2))
+        };
+        // as is a reflection into the microsurface
+        if wi.z() <= 0.0 {
+            return None;
+        }
+        self.weigh(wo, wi)
+    }
+
+    // f cos / pdf for a sampled direction
+    fn weigh(&self, wo: Vec3, wi: Vec3) -> Option<(Colour, Vec3)> {
+        let (f, pdf) = self.eval(wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some((f * wi.z().abs() / pdf, wi))
+    }
+
+    // bsdf value and the pdf of sampling wi with the current odds
+    fn eval(&self, wo: Vec3, wi: Vec3) -> (Colour, f64) {
+        let mut f = Colour::new_z();
+        let mut pdf = 0.0;
+        if wi.z() < 0.0 {
+            // only the glass lets light through
+            if self.weights[2] > 0.0 {
+                let (ft, pt) = self.refraction(wo, wi);
+                f += self.weights[2] * ft * self.base;
+                pdf += self.odds[2] * pt;
+            }
+            return (f, pdf);
+        }
+
+        let wm = Vec3::unit_vector(wo + wi);
+        let cos_d = Vec3::dot(wi, wm);
+        if self.weights[0] > 0.0 {
+            f += self.weights[0] * self.diffuse(wo, wi, cos_d);
+            pdf += self.odds[0] * wi.z() / pi;
+        }
+
+        let ggx = self.distrib.d(wm) * self.distrib.g(wo, wi) / (4.0 * wo.z() * wi.z());
+        let reflect_pdf = self.distrib.pdf(wo, wm) / (4.0 * Vec3::dot(wo, wm));
+        if self.weights[1] > 0.0 {
+            f += self.weights[1] * ggx * schlick(self.cspec0, cos_d);
+            pdf += self.odds[1] * reflect_pdf;
+        }
+        if self.weights[2] > 0.0 {
+            let fr = fr_dielectric(Vec3::dot(wo, wm), self.eta);
+            f += Colour::new(1.0, 1.0, 1.0) * (self.weights[2] * ggx * fr);
+            pdf += self.odds[2] * reflect_pdf * fr;
+        }
+        if self.weights[3] > 0.0 {
+            let d = gtr1(wm.z(), self.coat_alpha);
+            let g = smith_g1(wo.z(), 0.25) * smith_g1(wi.z(), 0.25);
+            let fc = 0.04 + 0.96 * schlick_weight(cos_d);
+            f += Colour::new(1.0, 1.0, 1.0) * (self.weights[3] * d * g * fc / (4.0 * wo.z() * wi.z()));
+            pdf += self.odds[3] * d * wm.z() / (4.0 * Vec3::dot(wo, wm));
+        }
+        (f, pdf)
+    }
+
+    // burley's diffuse with the subsurface approximation blended in, plus sheen
+    fn diffuse(&self, wo: Vec3, wi: Vec3, cos_d: f64) -> Colour {
+        let (fl, fv) = (schlick_weight(wi.z()), schlick_weight(wo.z()));
+        let rr = 2.0 * self.roughness * cos_d * cos_d;
+        let lambert = (1.0 - 0.5 * fl) * (1.0 - 0.5 * fv);
+        let retro = rr * (fl + fv + fl * fv * (rr - 1.0));
+
+        let fss90 = cos_d * cos_d * self.roughness;
+        let fss = (1.0 + (fss90 - 1.0) * fl) * (1.0 + (fss90 - 1.0) * fv);
+        let ss = 1.25 * (fss * (1.0 / (wi.z() + wo.z()) - 0.5) + 0.5);
+        let shape = (1.0 - self.subsurface) * (lambert + retro) + self.subsurface * ss;
+
+        let lum = luminance(self.base);
+        let tint = if lum > 0.0 { self.base / lum } else { Colour::new(1.0, 1.0, 1.0) };
+        let sheen = self.sheen * schlick_weight(cos_d) * (0.5 * tint + 0.5);
+        self.base * (shape / pi) + sheen
+    }
+
+    // the refracted half of the rough glass lobe and its pdf (walter et al. 2007)
+    fn refraction(&self, wo: Vec3, wi: Vec3) -> (f64, f64) {
+        let mut wm = Vec3::unit_vector(self.eta * wi + wo);
+        if wm.z() < 0.0 {
+            wm = -wm;
+        }
+        let (cos_om, cos_im) = (Vec3::dot(wo, wm), Vec3::dot(wi, wm));
+        if cos_om <= 0.0 || cos_im >= 0.0 {
+            return (0.0, 0.0);
+        }
+        let denom = (cos_im + cos_om / self.eta).powi(2);
+        let t = 1.0 - fr_dielectric(cos_om, self.eta);
+        let f = self.distrib.d(wm) * self.distrib.g(wo, wi) * t * (cos_im * cos_om / (wi.z() * wo.z() * denom)).abs();
+        let pdf = self.distrib.pdf(wo, wm) * cos_im.abs() / denom * t;
+        (f, pdf)
+    }
+}
+
+fn luminance(c: Colour) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+fn schlick_weight(cos: f64) -> f64 {
+    (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+fn schlick(f0: Colour, cos: f64) -> Colour {
+    f0 + (Colour::new(1.0, 1.0, 1.0) - f0) * schlick_weight(cos)
+}
+
+// berry's distribution, the long tailed lobe the clearcoat uses
+fn gtr1(cos_m: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (pi * a2.ln() * (1.0 + (a2 - 1.0) * cos_m * cos_m))
+}
+
+fn sample_gtr1(alpha: f64, u1: f64, u2: f64) -> Vec3 {
+    let a2 = alpha * alpha;
+    let cos = ((1.0 - a2.powf(1.0 - u1)) / (1.0 - a2)).max(0.0).sqrt();
+    let sin = (1.0 - cos * cos).max(0.0).sqrt();
+    let phi = 2.0 * pi * u2;
+    Vec3::new(sin * phi.cos(), sin * phi.sin(), cos)
+}
+
+// separable smith masking for an isotropic ggx of the given alpha
+fn smith_g1(cos: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let c2 = cos * cos;
+    2.0 * cos / (cos + (a2 + c2 - a2 * c2).sqrt())
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{Lobes, Principled};
+    use crate::hit::HitRecord;
+    use crate::texture::{Channel, ImageChannel, ImageTexture};
+    use crate::vec3::{Colour, Vec3};
+    use std::sync::Arc;
+
+    #[test]
+    fn lobe_sampling_matches_the_bsdf() {
+        // the mean sampled weight has to equal the bsdf integrated against |cos| over the
+        // sphere, which only holds if the mixture pdf covers every lobe
+        let mut rng = rand::thread_rng();
+        let wo = Vec3::unit_vector(Vec3::new(0.5, 0.1, 0.8));
+        for (metallic, transmission, eta) in [(0.0, 0.0, 1.5), (0.3, 0.6, 1.5), (0.0, 1.0, 1.0 / 1.5)] {
+            let mut lobes = Lobes::new(
+                Colour::new(0.8, 0.5, 0.3), metallic, 0.6, 0.5, 0.5, 0.5, 0.0, transmission, 0.5, eta,
+            );
+            assert!(lobes.set_odds(wo));
+
+            let (nz, nphi) = (2000, 400);
+            let (dz, dphi) = (2.0 / nz as f64, 2.0 * std::f64::consts::PI / nphi as f64);
+            let mut integral = 0.0;
+            for i in 0..nz {
+                let z = -1.0 + (i as f64 + 0.5) * dz;
+                let r = (1.0 - z * z).sqrt();
+                for j in 0..nphi {
+                    let phi = (j as f64 + 0.5) * dphi;
+                    let (f, _) = lobes.eval(wo, Vec3::new(r * phi.cos(), r * phi.sin(), z));
+                    integral += f.y() * z.abs() * dz * dphi;
+                }
+            }
+
+            let n = 200_000;
+            let mut mean = 0.0;
+            for _ in 0..n {
+                if let Some((weight, _)) = lobes.sample(wo, &mut rng) {
+                    mean += weight.y() / n as f64;
+                }
+            }
+            assert!((mean - integral).abs() < 0.015 * integral, "sampled {mean} integrated {integral}");
+        }
+    }
+
+    #[test]
+    fn metallic_and_roughness_from_an_image() {
+        // packed as glTF does, roughness in green and metallic in blue: a rough
+        //  dielectric on the left half, a smoother metal on the right
+        let image = Arc::new(ImageTexture::new(2, 1, vec![Colour::new(0.0, 0.8, 0.0), Colour::new(0.0, 0.2, 1.0)]).unwrap());
+        let base = Colour::new(0.8, 0.5, 0.3);
+        let material = Principled::new(base)
+            .roughness(ImageChannel::new(image.clone(), Channel::Green))
+            .metallic(ImageChannel::new(image, Channel::Blue))
+            .ir(1.3);
+
+        let mut record = HitRecord::new();
+        record.front_face = true;
+        (record.u, record.v) = (0.25, 0.5);
+        let left = material.lobes(&record);
+        assert!((left.roughness - 0.8).abs() < 1e-12 && left.weights[0] == 1.0);
+        assert!((left.cspec0 - Colour::new(0.04, 0.04, 0.04)).length() < 1e-12);
+        assert_eq!(left.eta, 1.3);
+        record.u = 0.75;
+        let right = material.lobes(&record);
+        assert!((right.roughness - 0.2).abs() < 1e-12 && right.weights[0] == 0.0);
+        assert!((right.cspec0 - base).length() < 1e-12);
+    }
+}
@@ -1,11 +1,45 @@
 use crate::constants::inf;
 use crate::hit::{HitRecord, Hittable};
+use crate::spectrum::{self, Wavelengths};
 use crate::vec3::{Colour, Point3, Vec3};
+use rand::Rng;
+
+/* What fills the space a ray travels through, as coefficients per unit length.
+ *  The default is empty space.
+ */
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Medium {
+    absorption: Colour,
+    scattering: Colour,
+}
+
+impl Medium {
+    pub fn new(absorption: Colour, scattering: Colour) -> Self {
+        Self { absorption, scattering }
+    }
+
+    pub fn absorbing(absorption: Colour) -> Self {
+        Self::new(absorption, Colour::new_z())
+    }
+
+    pub fn absorption(&self) -> Colour {
+        self.absorption
+    }
+
+    pub fn scattering(&self) -> Colour {
+        self.scattering
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct Ray {
     ori: Point3,
     dir: Vec3,
+    // whatever the ray is travelling through, None means it carries on in the same
+    //  medium as the ray that spawned it
+    medium: Option<Medium>,
+    // set in spectral mode, None means the same wavelengths as the ray that spawned it
+    wavelengths: Option<Wavelengths>,
 }
 
 impl Ray {
@@ -13,6 +47,82 @@ impl Ray {
         Self {
             ori: origin,
             dir: direction,
+            medium: None,
+            wavelengths: None,
+        }
+    }
+
+    // the ray starts off inside a medium absorbing at the given rate per unit length
+    pub fn with_absorption(self, absorption: Colour) -> Self {
+        self.with_medium(Medium::absorbing(absorption))
+    }
+
+    pub fn with_medium(mut self, medium: Medium) -> Self {
+        self.medium = Some(medium);
+        self
+    }
+
+    pub fn medium(&self) -> Medium {
+        self.medium.unwrap_or_default()
+    }
+
+    // the ray carries values at these wavelengths rather than RGB
+    pub fn with_wavelengths(mut self, wavelengths: Wavelengths) -> Self {
+        self.wavelengths = Some(wavelengths);
+        self
+    }
+
+    pub fn wavelengths(&self) -> Option<Wavelengths> {
+        self.wavelengths
+    }
+
+    // an RGB input such as an albedo, as the ray sees it
+    pub fn colour(&self, rgb: Colour) -> Colour {
+        match self.wavelengths {
+            Some(w) => spectrum::upsample(rgb, w.lambda()),
+            None => rgb,
+        }
+    }
+
+    pub fn absorption(&self) -> Colour {
+        self.medium().absorption
+    }
+
+    // beer-lambert falloff along the ray up to t
+    pub fn transmittance(&self, t: f64) -> Colour {
+        let medium = self.medium();
+        let a = self.colour(medium.absorption + medium.scattering);
+        if a.near_zero() {
+            return Colour::new(1.0, 1.0, 1.0);
+        }
+        let d = t * self.dir.length();
+        Colour::new((-a.x() * d).exp(), (-a.y() * d).exp(), (-a.z() * d).exp())
+    }
+
+    /* @brief How far the ray gets through a scattering medium before it scatters, None
+     *  if it makes it to t_max, with the weight of either outcome. The distance is
+     *  sampled in one channel picked at random and weighted against all three.
+     */
+    pub fn sample_medium(&self, t_max: f64) -> (Option<f64>, Colour) {
+        let medium = self.medium();
+        let sigma_s = self.colour(medium.scattering);
+        if sigma_s.near_zero() {
+            return (None, self.transmittance(t_max));
+        }
+        let sigma_t = self.colour(medium.absorption) + sigma_s;
+
+        let mut rng = rand::thread_rng();
+        let channel = rng.gen_range(0..3);
+        let distance = -(1.0 - rng.gen_range(0.0..1.0_f64)).ln() / sigma_t[channel];
+        let t = distance / self.dir.length();
+        if t < t_max {
+            let tr = self.transmittance(t);
+            let pdf = (sigma_t.x() * tr.x() + sigma_t.y() * tr.y() + sigma_t.z() * tr.z()) / 3.0;
+            (Some(t), sigma_s * tr / pdf)
+        } else {
+            let tr = self.transmittance(t_max);
+            let pdf = (tr.x() + tr.y() + tr.z()) / 3.0;
+            (None, tr / pdf)
         }
     }
 
@@ -29,24 +139,70 @@ impl Ray {
     }
 }
 
+// scatters inside media one path may take, counted apart from the surface bounces
+const MAX_MEDIUM_STEPS: usize = 4096;
+
 pub fn ray_colour(ray: Ray, world: &impl Hittable, depth: usize) -> Vec3 {
+    let mut steps = MAX_MEDIUM_STEPS;
+    trace(ray, world, depth, &mut steps)
+}
+
+fn trace(ray: Ray, world: &impl Hittable, depth: usize, steps: &mut usize) -> Vec3 {
     if depth == 0 {
         return Colour::new(0.0, 0.0, 0.0)
     }
+    let mut ray = ray;
     let mut rec = HitRecord::default();
     let mut scattered = Ray::default();
     let mut attenuation = Colour::default();
-    if world.hit(&ray, 0.001, inf, &mut rec) {
+    let mut throughput = Colour::new(1.0, 1.0, 1.0);
+    // rays leaving a surface skip past it, but a walk starts inside the medium and can
+    //  be right up against the boundary it has to find
+    let mut t_min = 0.001;
+    while world.hit(&ray, t_min, inf, &mut rec) {
+        let (scatter_at, transmittance) = ray.sample_medium(rec.t);
+        throughput *= transmittance;
+        if let Some(t) = scatter_at {
+            // scattered by the medium before it got to the surface, the same in every direction
+            if *steps == 0 {
+                return Colour::new(0.0, 0.0, 0.0)
+            }
+            *steps -= 1;
+            let mut walk = Ray::new(ray.at(t), Vec3::rand_unit_vector());
+            walk.medium = ray.medium;
+            walk.wavelengths = ray.wavelengths;
+            ray = walk;
+            t_min = 0.0;
+            continue;
+        }
+        let medium = ray.medium;
+        let wavelengths = ray.wavelengths;
         if rec.material.as_ref().map(|x| x.scatter(ray, &rec, &mut attenuation, &mut scattered)).unwrap() {
-            return attenuation * ray_colour(scattered, world, depth - 1)
+            // unless the material moved it into another medium the ray stays where it was
+            if scattered.medium.is_none() {
+                scattered.medium = medium;
+            }
+            if scattered.wavelengths.is_none() {
+                scattered.wavelengths = wavelengths;
+            }
+            let mut attenuation = scattered.colour(attenuation);
+            if scattered.wavelengths.is_some_and(|w| w.terminated()) && !wavelengths.is_some_and(|w| w.terminated()) {
+                // the path just lost its secondary wavelengths, the hero stands in for all three
+                attenuation = Colour::new(3.0 * attenuation.x(), 0.0, 0.0);
+            }
+            return throughput * attenuation * trace(scattered, world, depth - 1, steps)
         }
         return Colour::new(0.0, 0.0, 0.0)
     }
+    if !(ray.absorption() + ray.medium().scattering).near_zero() {
+        // lost inside an absorbing medium
+        return Colour::new(0.0, 0.0, 0.0)
+    }
     let unit_dir = Vec3::unit_vector(ray.direction());
     // transforms t to between 0 and 1
     let t = 0.5 * (unit_dir.y() + 1.0);
     // blends from white to something light blue
-    (1.0 - t) * Colour::new(1.0, 1.0, 1.0) + t * Colour::new(0.5, 0.7, 1.0)
+    throughput * ray.colour((1.0 - t) * Colour::new(1.0, 1.0, 1.0) + t * Colour::new(0.5, 0.7, 1.0))
 }
 
 pub fn hits_sphere(sph_center: Vec3, radius: f64, ray: &Ray) -> f64 {
@@ -64,7 +220,7 @@ pub fn hits_sphere(sph_center: Vec3, radius: f64, ray: &Ray) -> f64 {
 
 pub fn sph_ray_colour(r: &Ray) -> Vec3 {
     let unit_dir = Vec3::unit_vector(r.direction());
-    let t = hits_sphere(Point3::new(0.0, 0.0, -1.0), 0.5, &r);
+    let t = hits_sphere(Point3::new(0.0, 0.0, -1.0), 0.5, r);
     if t > 0.0 {
         let norm = Vec3::unit_vector(r.at(t) - Vec3::new(0.0, 0.0, -1.0));
         0.5 * (Colour::new(norm.x(), norm.y(), norm.z()) + 1.0)
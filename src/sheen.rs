@@ -0,0 +1,180 @@
+use crate::constants::pi;
+use crate::hit::{HitRecord, Material};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::{SolidColour, Texture};
+use crate::vec3::{Colour, Vec3};
+use rand::Rng;
+use std::sync::Arc;
+
+/* Velvet and cloth. Estevez and Kulla's sheen, the "charlie" distribution of fibres
+ *  standing up off the surface with their fitted shadowing term, giving the bright
+ *  rim at grazing angles. Over a base material the base only gets the light the sheen
+ *  did not reflect, by the sheen's directional albedo, so the pair never gains energy.
+ */
+pub struct Sheen {
+    colour: Arc<dyn Texture + Sync + Send>,
+    lobe: Charlie,
+    base: Option<Arc<dyn Material + Sync + Send>>,
+    // directional albedo of the lobe against cos theta_o, for the base's share
+    albedo: Vec<f64>,
+}
+
+impl Sheen {
+    const ALBEDO_SAMPLES: usize = 32;
+
+    // sheen on its own, whatever it doesn't reflect is absorbed
+    pub fn new(colour: Colour, roughness: f64) -> Self {
+        Self::textured(Arc::new(SolidColour::new(colour)), roughness)
+    }
+
+    pub fn textured(colour: Arc<dyn Texture + Sync + Send>, roughness: f64) -> Self {
+        let lobe = Charlie::new(roughness);
+        let albedo = (0..Self::ALBEDO_SAMPLES)
+            .map(|i| lobe.albedo((i as f64 + 0.5) / Self::ALBEDO_SAMPLES as f64))
+            .collect();
+        Self { colour, lobe, base: None, albedo }
+    }
+
+    // e.g. a Lambertian or OrenNayar for velvet
+    pub fn over(mut self, base: Arc<dyn Material + Sync + Send>) -> Self {
+        self.base = Some(base);
+        self
+    }
+
+    fn albedo(&self, cos_theta_o: f64) -> f64 {
+        let x = (cos_theta_o.clamp(0.0, 1.0) * Self::ALBEDO_SAMPLES as f64 - 0.5).max(0.0);
+        let i = (x as usize).min(Self::ALBEDO_SAMPLES - 2);
+        let t = (x - i as f64).min(1.0);
+        (1.0 - t) * self.albedo[i] + t * self.albedo[i + 1]
+    }
+}
+
+impl Material for Sheen {
+    fn scatter(&self, ray: Ray, record: &HitRecord, attenuation: &mut Colour, scattered: &mut Ray) -> bool {
+        let frame = Onb::from_w(record.norm());
+        let wo = frame.to_local(-Vec3::unit_vector(ray.direction()));
+        if wo.z() <= 0.0 {
+            return false;
+        }
+        let colour = self.colour.value(record);
+
+        // the odds of the sheen are what it reflects, and the base gets the rest
+        let odds = match &self.base {
+            Some(base) => {
+                let odds = (self.albedo(wo.z()) * colour.x().max(colour.y()).max(colour.z())).min(1.0);
+                if odds <= rand::thread_rng().gen_range(0.0..1.0) {
+                    return base.scatter(ray, record, attenuation, scattered);
+                }
+                odds
+            }
+            None => 1.0,
+        };
+        let wi = Vec3::rand_cosine_direction();
+        // f cos / pdf with the cosine sampling pdf of cos / pi
+        *attenuation = colour * (self.lobe.f(wo, wi) * pi / odds);
+        *scattered = Ray::new(record.point(), frame.local(wi));
+        true
+    }
+}
+
+// the sheen lobe itself, in the shading frame
+#[derive(Debug, Clone, Copy)]
+struct Charlie {
+    r: f64,
+}
+
+impl Charlie {
+    fn new(roughness: f64) -> Self {
+        // the shadowing fit only holds down to about here
+        Self { r: roughness.clamp(0.07, 1.0) }
+    }
+
+    fn d(&self, wm: Vec3) -> f64 {
+        let sin = (1.0 - wm.z() * wm.z()).max(0.0).sqrt();
+        (2.0 + 1.0 / self.r) * sin.powf(1.0 / self.r) / (2.0 * pi)
+    }
+
+    fn l(&self, x: f64) -> f64 {
+        let t = (1.0 - self.r) * (1.0 - self.r);
+        let lerp = |a: f64, b: f64| (1.0 - t) * a + t * b;
+        let (a, b, c) = (lerp(25.3245, 21.5473), lerp(3.32435, 3.82987), lerp(0.16801, 0.19823));
+        let (d, e) = (lerp(-1.27393, -1.97760), lerp(-4.85967, -4.32054));
+        a / (1.0 + b * x.powf(c)) + d * x + e
+    }
+
+    fn lambda(&self, cos: f64) -> f64 {
+        if cos < 0.5 {
+            self.l(cos).exp()
+        } else {
+            (2.0 * self.l(0.5) - self.l(1.0 - cos)).exp()
+        }
+    }
+
+    fn f(&self, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let wm = Vec3::unit_vector(wo + wi);
+        let g = 1.0 / (1.0 + self.lambda(wo.z()) + self.lambda(wi.z()));
+        self.d(wm) * g / (4.0 * wo.z() * wi.z())
+    }
+
+    // midpoint rule over the hemisphere of wi
+    fn albedo(&self, cos_theta_o: f64) -> f64 {
+        let wo = Vec3::new((1.0 - cos_theta_o * cos_theta_o).sqrt(), 0.0, cos_theta_o);
+        let (nz, nphi) = (64, 64);
+        let (dz, dphi) = (1.0 / nz as f64, 2.0 * pi / nphi as f64);
+        let mut sum = 0.0;
+        for i in 0..nz {
+            let z = (i as f64 + 0.5) * dz;
+            let r = (1.0 - z * z).sqrt();
+            for j in 0..nphi {
+                let phi = (j as f64 + 0.5) * dphi;
+                sum += self.f(wo, Vec3::new(r * phi.cos(), r * phi.sin(), z)) * z * dz * dphi;
+            }
+        }
+        sum
+    }
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{Charlie, Sheen};
+    use crate::hit::{HitRecord, Lambertian, Material};
+    use crate::ray::Ray;
+    use crate::vec3::{Colour, Point3, Vec3};
+    use std::sync::Arc;
+
+    #[test]
+    fn charlie_is_normalised_and_layering_keeps_energy() {
+        // projected microfacet area comes to one
+        for lobe in [Charlie::new(0.3), Charlie::new(0.8)] {
+            let (nz, nphi) = (2000, 100);
+            let (dz, dphi) = (1.0 / nz as f64, 2.0 * std::f64::consts::PI / nphi as f64);
+            let mut area = 0.0;
+            for i in 0..nz {
+                let z = (i as f64 + 0.5) * dz;
+                let r = (1.0 - z * z).sqrt();
+                area += nphi as f64 * lobe.d(Vec3::new(r, 0.0, z)) * z * dz * dphi;
+            }
+            assert!((area - 1.0).abs() < 0.01, "{area}");
+        }
+
+        let mut record = HitRecord::new();
+        record.norm = Vec3::new(0.0, 0.0, 1.0);
+        record.front_face = true;
+        let velvet = Sheen::new(Colour::new(1.0, 1.0, 1.0), 0.5).over(Arc::new(Lambertian::new(Colour::new(1.0, 1.0, 1.0))));
+        let n = 50_000;
+        let mut mean = 0.0;
+        for _ in 0..n {
+            let ray = Ray::new(Point3::new(-3.0, 0.0, 1.0), Vec3::new(3.0, 0.0, -1.0));
+            let (mut attenuation, mut scattered) = (Colour::new_z(), Ray::default());
+            if velvet.scatter(ray, &record, &mut attenuation, &mut scattered) {
+                mean += attenuation.x() / n as f64;
+            }
+        }
+        assert!(mean < 1.02 && mean > 0.8, "{mean}");
+    }
+}
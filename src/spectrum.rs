@@ -0,0 +1,210 @@
+use crate::vec3::{Colour, Vec3};
+use std::sync::OnceLock;
+
+// range the wavelengths are drawn from, in nm
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+
+/* The wavelengths one path carries, a hero and two more rotated evenly around the
+ *  range from it, one per lane of a Vec3. In spectral mode every Colour along the path
+ *  holds values at these wavelengths rather than RGB.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Wavelengths {
+    lambda: Vec3,
+    // after a wavelength dependent bounce only the hero is still valid
+    terminated: bool,
+}
+
+impl Wavelengths {
+    // u uniform in 0..1 places the hero
+    pub fn sample(u: f64) -> Self {
+        let at = |offset: f64| LAMBDA_MIN + (u + offset).fract() * (LAMBDA_MAX - LAMBDA_MIN);
+        Self { lambda: Vec3::new(at(0.0), at(1.0 / 3.0), at(2.0 / 3.0)), terminated: false }
+    }
+
+    // e.g. dispersion, where the other wavelengths would have bent elsewhere
+    pub fn terminate_secondary(mut self) -> Self {
+        self.terminated = true;
+        self
+    }
+
+    pub fn terminated(&self) -> bool {
+        self.terminated
+    }
+
+    pub fn lambda(&self) -> Vec3 {
+        self.lambda
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda.x()
+    }
+}
+
+/* Wavelength dependent refractive index, coefficients take the wavelength in
+ *  micrometres as the glass catalogues do
+ */
+#[derive(Debug, Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / lambda^2
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum of b lambda^2 / (lambda^2 - c)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    // schott N-BK7, the common crown glass
+    pub fn bk7() -> Self {
+        Self::Sellmeier { b: [1.03961212, 0.231792344, 1.01046945], c: [0.00600069867, 0.0200179144, 103.560653] }
+    }
+
+    // schott F2
+    pub fn flint() -> Self {
+        Self::Sellmeier { b: [1.34533359, 0.209073176, 0.937357162], c: [0.00997743871, 0.0470450767, 111.886764] }
+    }
+
+    pub fn diamond() -> Self {
+        Self::Sellmeier { b: [4.3356, 0.3306, 0.0], c: [0.1060 * 0.1060, 0.1750 * 0.1750, 0.0] }
+    }
+
+    // index at a wavelength in nm
+    pub fn ior(&self, lambda: f64) -> f64 {
+        let l = lambda / 1000.0;
+        match self {
+            Self::Cauchy { a, b } => a + b / (l * l),
+            Self::Sellmeier { b, c } => {
+                let sum: f64 = b.iter().zip(c).map(|(b, c)| b * l * l / (l * l - c)).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    // at the helium d line, what the catalogues quote as the index
+    pub fn ior_d(&self) -> f64 {
+        self.ior(587.56)
+    }
+}
+
+/* @brief Values at the given wavelengths of a smooth reflectance spectrum with
+ *  roughly the given linear RGB (Smits 1999)
+ */
+pub fn upsample(rgb: Colour, lambda: Vec3) -> Vec3 {
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+    // the smallest channel is white, the middle one a secondary and the rest a primary
+    let white = [(r.min(g).min(b), &WHITE)];
+    let rest = if r <= g && r <= b {
+        if g <= b { [(g - r, &CYAN), (b - g, &BLUE)] } else { [(b - r, &CYAN), (g - b, &GREEN)] }
+    } else if g <= r && g <= b {
+        if r <= b { [(r - g, &MAGENTA), (b - r, &BLUE)] } else { [(b - g, &MAGENTA), (r - b, &RED)] }
+    } else if r <= g {
+        [(r - b, &YELLOW), (g - r, &GREEN)]
+    } else {
+        [(g - b, &YELLOW), (r - g, &RED)]
+    };
+
+    let mut out = Vec3::new_z();
+    for i in 0..3 {
+        out[i] = white.iter().chain(rest.iter()).map(|(w, basis)| w * smits(basis, lambda[i])).sum();
+    }
+    out
+}
+
+/* @brief Turns one sample's values at its wavelengths back into linear sRGB. White
+ *  balanced so a flat spectrum of 1 comes out as (1, 1, 1) on average.
+ */
+pub fn to_rgb(values: Vec3, wavelengths: Wavelengths) -> Colour {
+    let mut xyz = Vec3::new_z();
+    for i in 0..3 {
+        xyz += values[i] * cie_xyz(wavelengths.lambda[i]);
+    }
+    // dividing by the pdf and by three lanes, against the integral below
+    let xyz = xyz * (LAMBDA_MAX - LAMBDA_MIN) / 3.0;
+    let rgb = xyz_to_srgb(xyz);
+    let white = white_rgb();
+    Colour::new(rgb.x() / white.x(), rgb.y() / white.y(), rgb.z() / white.z())
+}
+
+// linear sRGB of the flat spectrum integrated over the range, 1nm at a time
+fn white_rgb() -> Colour {
+    static WHITE_RGB: OnceLock<Colour> = OnceLock::new();
+    *WHITE_RGB.get_or_init(|| {
+        let mut xyz = Vec3::new_z();
+        let mut lambda = LAMBDA_MIN + 0.5;
+        while lambda < LAMBDA_MAX {
+            xyz += cie_xyz(lambda);
+            lambda += 1.0;
+        }
+        xyz_to_srgb(xyz)
+    })
+}
+
+fn xyz_to_srgb(xyz: Vec3) -> Colour {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+    Colour::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+// CIE 1931 matching functions as the multi-lobe gaussian fit of Wyman et al. 2013
+fn cie_xyz(lambda: f64) -> Vec3 {
+    let g = |mu: f64, s1: f64, s2: f64| {
+        let t = (lambda - mu) / if lambda < mu { s1 } else { s2 };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+// smits' basis spectra, ten bins evenly over 380..720nm
+type Basis = [f64; 10];
+const WHITE: Basis = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const CYAN: Basis = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const MAGENTA: Basis = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const YELLOW: Basis = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const RED: Basis = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const GREEN: Basis = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const BLUE: Basis = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// linear between bin centres, held flat past the ends
+fn smits(basis: &Basis, lambda: f64) -> f64 {
+    let x = ((lambda - 380.0) / 34.0 - 0.5).clamp(0.0, 9.0);
+    let i = (x as usize).min(8);
+    let t = x - i as f64;
+    (1.0 - t) * basis[i] + t * basis[i + 1]
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::{to_rgb, upsample, Dispersion, Wavelengths};
+    use crate::vec3::Colour;
+
+    #[test]
+    fn rgb_survives_the_round_trip() {
+        for rgb in [Colour::new(1.0, 1.0, 1.0), Colour::new(0.8, 0.3, 0.2), Colour::new(0.1, 0.4, 0.7)] {
+            let n = 3000;
+            let mut out = Colour::new_z();
+            for i in 0..n {
+                let w = Wavelengths::sample((i as f64 + 0.5) / n as f64);
+                out += to_rgb(upsample(rgb, w.lambda()), w) / n as f64;
+            }
+            assert!((out - rgb).length() < 0.05, "{rgb:?} came back as {out:?}");
+        }
+    }
+
+    #[test]
+    fn catalogue_indices() {
+        assert!((Dispersion::bk7().ior_d() - 1.5168).abs() < 1e-4);
+        assert!((Dispersion::flint().ior_d() - 1.6200).abs() < 1e-3);
+        assert!((Dispersion::diamond().ior_d() - 2.417).abs() < 2e-3);
+        // normal dispersion, blue bends more
+        let cauchy = Dispersion::Cauchy { a: 1.5046, b: 0.0042 };
+        assert!(cauchy.ior(450.0) > cauchy.ior(650.0));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::mesh::MeshData;
+use crate::vec3::{Point3, Vec3};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/* STL reader for both the ascii and binary flavours. STL has no shared vertices,
+ *  every facet brings its own three corners, and the stored facet normals are
+ *  ignored in favour of the winding.
+ */
+
+/* @brief Reads a .stl file, MeshData::summary says what was in it
+ */
+pub fn load(path: impl AsRef<Path>) -> io::Result<MeshData> {
+    parse(&fs::read(path)?)
+}
+
+pub fn parse(bytes: &[u8]) -> io::Result<MeshData> {
+    // binary files are allowed to start with "solid" too, so trust the size first
+    if bytes.len() >= 84 {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        if bytes.len() == 84 + 50 * count {
+            return Ok(parse_binary(&bytes[84..], count));
+        }
+    }
+    if bytes.trim_ascii_start().starts_with(b"solid") {
+        return parse_ascii(bytes);
+    }
+    Err(invalid("neither ascii nor binary stl"))
+}
+
+fn parse_binary(body: &[u8], count: usize) -> MeshData {
+    let mut mesh = MeshData::default();
+    let read = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64;
+    for facet in body.chunks_exact(50).take(count) {
+        // 12 bytes of normal, 3 corners of 12 bytes, then 2 attribute bytes
+        let base = mesh.positions.len();
+        for corner in 0..3 {
+            let o = 12 + 12 * corner;
+            mesh.positions.push(Point3::new(read(&facet[o..]), read(&facet[o + 4..]), read(&facet[o + 8..])));
+        }
+        mesh.indices.push([base, base + 1, base + 2]);
+    }
+    mesh.check_indices();
+    mesh
+}
+
+fn parse_ascii(bytes: &[u8]) -> io::Result<MeshData> {
+    let text = std::str::from_utf8(bytes).map_err(|_| invalid("non utf8 ascii stl"))?;
+    let mut mesh = MeshData::default();
+    let mut corners = Vec::<Point3>::with_capacity(3);
+    let mut tokens = text.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match token {
+            "vertex" => {
+                let mut coord = || -> io::Result<f64> {
+                    tokens
+                        .next()
+                        .and_then(|t| t.parse::<f64>().ok())
+                        .ok_or_else(|| invalid("bad vertex"))
+                };
+                corners.push(Vec3::new(coord()?, coord()?, coord()?));
+            }
+            "endloop" => {
+                // polygons with more than three corners get fanned
+                let base = mesh.positions.len();
+                for i in 1..corners.len().saturating_sub(1) {
+                    mesh.indices.push([base, base + i, base + i + 1]);
+                }
+                mesh.positions.append(&mut corners);
+            }
+            _ => {}
+        }
+    }
+    mesh.check_indices();
+    Ok(mesh)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("stl: {msg}"))
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::parse;
+
+    #[test]
+    fn ascii_and_binary() {
+        let ascii = b"solid cube
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0.5
+    endloop
+  endfacet
+endsolid cube
+";
+        let mesh = parse(ascii).unwrap();
+        assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+        assert_eq!(mesh.positions[2].z(), 0.5);
+
+        // binary header that happens to start with "solid"
+        let mut binary = b"solid but binary".to_vec();
+        binary.resize(80, 0);
+        binary.extend_from_slice(&2u32.to_le_bytes());
+        for facet in 0..2 {
+            binary.extend_from_slice(&[0; 12]);
+            for c in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, facet as f32] {
+                binary.extend_from_slice(&c.to_le_bytes());
+            }
+            binary.extend_from_slice(&[0, 0]);
+        }
+        let mesh = parse(&binary).unwrap();
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [3, 4, 5]]);
+        assert_eq!(mesh.positions[5].z(), 1.0);
+        assert_eq!(mesh.summary().triangles, 2);
+    }
+}
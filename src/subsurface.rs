@@ -0,0 +1,83 @@
+use crate::fresnel::fr_dielectric;
+use crate::hit::{HitRecord, Material};
+use crate::ray::{Medium, Ray};
+use crate::vec3::{Colour, Vec3};
+use rand::Rng;
+
+/* Skin, wax, marble and milk. A smooth dielectric boundary around a scattering
+ *  medium: light that refracts in takes a random walk inside the closed object,
+ *  reflecting off the inside of the boundary as the fresnel term says, until it
+ *  refracts back out somewhere else or is absorbed.
+ */
+pub struct Subsurface {
+    ir: f64,
+    medium: Medium,
+}
+
+impl Subsurface {
+    /* @brief mean_free_path is the average distance between scattering events and
+     *  albedo the fraction of light surviving each one, both per channel
+     */
+    pub fn new(ir: f64, mean_free_path: Colour, albedo: Colour) -> Self {
+        let mut sigma_t = Colour::new_z();
+        for i in 0..3 {
+            sigma_t[i] = 1.0 / mean_free_path[i].max(1e-9);
+        }
+        let scattering = albedo * sigma_t;
+        Self { ir, medium: Medium::new(sigma_t - scattering, scattering) }
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, ray: Ray, record: &HitRecord, attenuation: &mut Colour, scattered: &mut Ray) -> bool {
+        *attenuation = Colour::new(1.0, 1.0, 1.0);
+        let eta = if record.front_face { self.ir } else { 1.0 / self.ir };
+        let unit_direction = Vec3::unit_vector(ray.direction());
+        let cos_theta = Vec3::dot(-unit_direction, record.norm());
+
+        *scattered = if fr_dielectric(cos_theta, eta) > rand::thread_rng().gen_range(0.0..1.0) {
+            // stays on whichever side it was, in the same medium
+            Ray::new(record.point(), Vec3::reflect(unit_direction, record.norm()))
+        } else if record.front_face {
+            Ray::new(record.point(), Vec3::refract(unit_direction, record.norm(), 1.0 / eta)).with_medium(self.medium)
+        } else {
+            Ray::new(record.point(), Vec3::refract(unit_direction, record.norm(), 1.0 / eta)).with_medium(Medium::default())
+        };
+        true
+    }
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::Subsurface;
+    use crate::hit::{HittableList, Sphere};
+    use crate::ray::{ray_colour, Ray};
+    use crate::vec3::{Colour, Point3, Vec3};
+    use std::sync::Arc;
+
+    #[test]
+    fn lossless_walks_come_back_out() {
+        // nothing absorbed, so every walk has to leave the sphere again and see the sky
+        let mut world = HittableList::new();
+        let material = Arc::new(Subsurface::new(1.3, Colour::new(0.25, 0.25, 0.25), Colour::new(1.0, 1.0, 1.0)));
+        world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material)));
+
+        let n = 2000;
+        let mut lost = 0;
+        for i in 0..n {
+            let x = -0.9 + 1.8 * i as f64 / n as f64;
+            let ray = Ray::new(Point3::new(x, 0.1, 5.0), Vec3::new(0.0, 0.0, -1.0));
+            // main's depth, the steps of the walk don't count against it
+            let c = ray_colour(ray, &world, 50);
+            if c.x() == 0.0 {
+                lost += 1;
+                continue;
+            }
+            // the sky runs from white to (0.5, 0.7, 1), and the walk can't add energy
+            assert!(c.x() > 0.5 - 1e-9 && c.x() < 1.0 + 1e-9, "{c:?}");
+            assert!(c.z() > 1.0 - 1e-9 && c.z() < 1.0 + 1e-9, "{c:?}");
+        }
+        assert!(lost < n / 100, "{lost} walks were lost");
+    }
+}
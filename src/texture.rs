@@ -0,0 +1,347 @@
+use crate::hit::HitRecord;
+use crate::mesh::MeshData;
+use crate::vec3::Colour;
+use std::fs;
+use std::io::{self, BufRead};
+use std::ops::{Add, Mul};
+use std::path::Path;
+use std::sync::Arc;
+
+pub trait Texture {
+    fn value(&self, record: &HitRecord) -> Colour;
+}
+
+// scalar inputs such as roughness, a plain f64 works as a constant
+pub trait FloatTexture {
+    fn value(&self, record: &HitRecord) -> f64;
+}
+
+impl FloatTexture for f64 {
+    fn value(&self, _: &HitRecord) -> f64 {
+        *self
+    }
+}
+
+pub struct SolidColour {
+    colour: Colour,
+}
+
+impl SolidColour {
+    pub fn new(colour: Colour) -> Self {
+        Self { colour }
+    }
+}
+
+impl Texture for SolidColour {
+    fn value(&self, _: &HitRecord) -> Colour {
+        self.colour
+    }
+}
+
+/* Colours stored on the vertices of a mesh, blended across each triangle.
+ *  Only meaningful on hits against the mesh it was made from, anything else comes
+ *  out mid grey.
+ */
+pub struct VertexColours {
+    mesh: Arc<MeshData>,
+}
+
+impl VertexColours {
+    // None when the mesh came without colours
+    pub fn new(mesh: Arc<MeshData>) -> Option<Self> {
+        mesh.colours.as_ref()?;
+        Some(Self { mesh })
+    }
+}
+
+impl Texture for VertexColours {
+    fn value(&self, record: &HitRecord) -> Colour {
+        let colours = self.mesh.colours.as_ref().unwrap();
+        let corners = self.mesh.indices.get(record.face).and_then(|&[i0, i1, i2]| {
+            Some((colours.get(i0)?, colours.get(i1)?, colours.get(i2)?))
+        });
+        match corners {
+            Some((c0, c1, c2)) => {
+                let b = record.bary;
+                b.x() * *c0 + b.y() * *c1 + b.z() * *c2
+            }
+            None => Colour::new(0.5, 0.5, 0.5),
+        }
+    }
+}
+
+// one texture scaled by another, e.g. a base colour factor times an image
+pub struct Product {
+    a: Arc<dyn Texture + Sync + Send>,
+    b: Arc<dyn Texture + Sync + Send>,
+}
+
+impl Product {
+    pub fn new(a: Arc<dyn Texture + Sync + Send>, b: Arc<dyn Texture + Sync + Send>) -> Self {
+        Self { a, b }
+    }
+}
+
+impl Texture for Product {
+    fn value(&self, record: &HitRecord) -> Colour {
+        self.a.value(record) * self.b.value(record)
+    }
+}
+
+/* PNG, JPEG or PGM image looked up by the hit's (u, v), v = 0 is the bottom row. Filtered
+ *  bilinearly and repeated outside [0, 1].
+ *  Colour images are squared back to linear the same way the ply loader treats its
+ *  colours, data such as normal maps should come through the linear loaders. Alpha
+ *  is kept as stored either way.
+ */
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Colour>,
+    // opaque everywhere unless the image had an alpha channel
+    alpha: Vec<f64>,
+}
+
+impl ImageTexture {
+    // rows top to bottom, as they are stored in the file
+    pub fn new(width: usize, height: usize, pixels: Vec<Colour>) -> Option<Self> {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            return None;
+        }
+        Some(Self { width, height, alpha: vec![1.0; pixels.len()], pixels })
+    }
+
+    // None unless there is one alpha value per pixel
+    pub fn with_alpha(mut self, alpha: Vec<f64>) -> Option<Self> {
+        if alpha.len() != self.pixels.len() {
+            return None;
+        }
+        self.alpha = alpha;
+        Some(self)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    pub fn load_linear(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::decode_linear(&fs::read(path)?)
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let mut image = Self::decode_linear(bytes)?;
+        for c in image.pixels.iter_mut() {
+            *c = *c * *c;
+        }
+        Ok(image)
+    }
+
+    pub fn decode_linear(bytes: &[u8]) -> io::Result<Self> {
+        let (width, height, channels, data) = if bytes.starts_with(b"\x89PNG") {
+            decode_png(bytes)?
+        } else if bytes.starts_with(&[0xff, 0xd8]) {
+            decode_jpeg(bytes)?
+        } else if bytes.starts_with(b"P2") || bytes.starts_with(b"P5") {
+            // graymaps may be 16 bit, so they skip the 8 bit path
+            let (width, height, values) = read_pgm(bytes)?;
+            let pixels = values.into_iter().map(|v| Colour::new(v, v, v)).collect();
+            return Self::new(width, height, pixels).ok_or_else(|| invalid("pixel data does not match the size"));
+        } else {
+            return Err(invalid("not a png, jpeg or pgm"));
+        };
+        let pixels = data
+            .chunks_exact(channels)
+            .map(|p| match p.len() {
+                1 | 2 => Colour::new(p[0] as f64, p[0] as f64, p[0] as f64),
+                _ => Colour::new(p[0] as f64, p[1] as f64, p[2] as f64),
+            } / 255.0)
+            .collect();
+        let image = Self::new(width, height, pixels).ok_or_else(|| invalid("pixel data does not match the size"))?;
+        if channels % 2 == 1 {
+            return Ok(image);
+        }
+        // grey plus alpha or rgba, alpha comes last
+        let alpha = data.chunks_exact(channels).map(|p| p[channels - 1] as f64 / 255.0).collect();
+        image.with_alpha(alpha).ok_or_else(|| invalid("pixel data does not match the size"))
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    // rows top to bottom
+    pub fn pixels(&self) -> &[Colour] {
+        &self.pixels
+    }
+
+    // bilinear opacity at the hit, 1 for images without alpha
+    pub fn alpha(&self, record: &HitRecord) -> f64 {
+        self.filter(record, |i| self.alpha[i])
+    }
+
+    fn filter<T>(&self, record: &HitRecord, texel: impl Fn(usize) -> T) -> T
+    where
+        T: Add<Output = T>,
+        f64: Mul<T, Output = T>,
+    {
+        let at = |x: i64, y: i64| {
+            let x = x.rem_euclid(self.width as i64) as usize;
+            let y = y.rem_euclid(self.height as i64) as usize;
+            texel(y * self.width + x)
+        };
+        let (x0, y0, fx, fy) = self.corner(record);
+        let top = (1.0 - fx) * at(x0, y0) + fx * at(x0 + 1, y0);
+        let bottom = (1.0 - fx) * at(x0, y0 + 1) + fx * at(x0 + 1, y0 + 1);
+        (1.0 - fy) * top + fy * bottom
+    }
+
+    // the texel up and left of the hit and how far past its centre the hit is
+    fn corner(&self, record: &HitRecord) -> (i64, i64, f64, f64) {
+        // texel centres sit at half integers
+        let x = record.u * self.width as f64 - 0.5;
+        let y = (1.0 - record.v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        (x0 as i64, y0 as i64, x - x0, y - y0)
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, record: &HitRecord) -> Colour {
+        self.filter(record, |i| self.pixels[i])
+    }
+}
+
+// which channel of an image a scalar input reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+/* One channel of an image as a scalar texture, e.g. roughness in green and metallic in
+ *  blue the way glTF packs them, or alpha as the opacity of an AlphaMask. Data like
+ *  this wants the image loaded linear.
+ */
+pub struct ImageChannel {
+    image: Arc<ImageTexture>,
+    channel: Channel,
+}
+
+impl ImageChannel {
+    pub fn new(image: Arc<ImageTexture>, channel: Channel) -> Self {
+        Self { image, channel }
+    }
+}
+
+impl FloatTexture for ImageChannel {
+    fn value(&self, record: &HitRecord) -> f64 {
+        match self.channel {
+            Channel::Alpha => self.image.alpha(record),
+            channel => self.image.value(record)[channel as usize],
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("image: {msg}"))
+}
+
+// width, height, channels and 8 bit samples
+fn decode_png(bytes: &[u8]) -> io::Result<(usize, usize, usize, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| invalid(&e.to_string()))?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut data).map_err(|e| invalid(&e.to_string()))?;
+    data.truncate(frame.buffer_size());
+    Ok((frame.width as usize, frame.height as usize, frame.color_type.samples(), data))
+}
+
+fn decode_jpeg(bytes: &[u8]) -> io::Result<(usize, usize, usize, Vec<u8>)> {
+    let mut decoder = zune_jpeg::JpegDecoder::new(bytes);
+    let data = decoder.decode().map_err(|e| invalid(&format!("{e:?}")))?;
+    let (width, height) = decoder.dimensions().ok_or_else(|| invalid("missing header"))?;
+    let channels = decoder.get_output_colorspace().map_or(3, |c| c.num_components());
+    Ok((width, height, channels, data))
+}
+
+/* @brief Parses an ascii (P2) or binary (P5) graymap into normalised samples,
+ *  binary samples are 2 bytes big endian when maxval exceeds 255
+ */
+pub(crate) fn read_pgm(mut reader: impl BufRead) -> io::Result<(usize, usize, Vec<f64>)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("pgm: {msg}"));
+
+    // header tokens, skipping comments, stops after the single whitespace following maxval
+    let mut tokens = Vec::<String>::new();
+    let mut token = String::new();
+    let mut byte = [0u8; 1];
+    while tokens.len() < 4 {
+        reader.read_exact(&mut byte)?;
+        match byte[0] {
+            b'#' => {
+                let mut comment = Vec::new();
+                reader.read_until(b'\n', &mut comment)?;
+            }
+            c if c.is_ascii_whitespace() => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c as char),
+        }
+    }
+
+    let binary = match tokens[0].as_str() {
+        "P2" => false,
+        "P5" => true,
+        _ => return Err(invalid("only P2 and P5 graymaps are supported")),
+    };
+    let parse = |s: &str| s.parse::<usize>().map_err(|_| invalid("bad header value"));
+    let (width, height, maxval) = (parse(&tokens[1])?, parse(&tokens[2])?, parse(&tokens[3])?);
+    if maxval == 0 || maxval > 65535 {
+        return Err(invalid("maxval out of range"));
+    }
+
+    let count = width * height;
+    let raw: Vec<usize> = if binary {
+        let bytes_per = if maxval > 255 { 2 } else { 1 };
+        let mut data = vec![0u8; count * bytes_per];
+        reader.read_exact(&mut data)?;
+        data.chunks(bytes_per)
+            .map(|c| if bytes_per == 2 { (c[0] as usize) << 8 | c[1] as usize } else { c[0] as usize })
+            .collect()
+    } else {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        text.split_whitespace()
+            .take(count)
+            .map(parse)
+            .collect::<io::Result<Vec<usize>>>()?
+    };
+    if raw.len() != count {
+        return Err(invalid("not enough samples"));
+    }
+    Ok((width, height, raw.into_iter().map(|s| s as f64 / maxval as f64).collect()))
+}
+
+// testing
+#[cfg(test)]
+mod test {
+    use super::read_pgm;
+
+    #[test]
+    fn pgm_16_bit() {
+        let mut data = b"P5\n# terrain\n2 2\n65535\n".to_vec();
+        data.extend_from_slice(&[0x00, 0x00, 0xff, 0xff, 0x80, 0x00, 0x00, 0x00]);
+        let (w, h, samples) = read_pgm(&data[..]).unwrap();
+        assert_eq!((w, h), (2, 2));
+        assert_eq!(samples[0], 0.0);
+        assert_eq!(samples[1], 1.0);
+        assert!((samples[2] - 0.5).abs() < 1e-4);
+
+        let (_, _, ascii) = read_pgm(&b"P2 2 1 10 5 10"[..]).unwrap();
+        assert_eq!(ascii, vec![0.5, 1.0]);
+    }
+}
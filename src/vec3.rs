@@ -1,4 +1,5 @@
 use rand::distributions::{Distribution, Uniform};
+use rand::Rng;
 use std::default::Default;
 use std::io::{self, Write};
 use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign};
@@ -97,7 +98,7 @@ impl Vec3 {
                 write!(out, " ")?;
             }
         }
-        write!(out, "\n")
+        writeln!(out)
     }
 
     pub fn colour_to_str(colour: Colour, samples_per_pix: usize) -> Box<str> {
@@ -112,7 +113,7 @@ impl Vec3 {
                 .as_str(),
             );
             if i < 2 {
-                colour_string.push_str(" ");
+                colour_string.push(' ');
             }
         }
         colour_string.into_boxed_str()
@@ -130,7 +131,7 @@ impl Vec3 {
     }
 
     pub fn rand_unit_vector() -> Vec3 {
-        return Self::unit_vector(Self::rand_in_unit_sphere());
+        Self::unit_vector(Self::rand_in_unit_sphere())
     }
 
     pub fn rand_in_unit_disk() -> Vec3 {
@@ -143,6 +144,15 @@ impl Vec3 {
             }
         }
     }
+
+    // cosine weighted direction about +z, pdf cos(theta)/pi
+    pub fn rand_cosine_direction() -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let r1: f64 = rng.gen_range(0.0..1.0);
+        let phi = 2.0 * std::f64::consts::PI * rng.gen_range(0.0..1.0);
+        let r = r1.sqrt();
+        Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - r1).sqrt())
+    }
     
     /* @brief A tolerance checking function for float points
      */